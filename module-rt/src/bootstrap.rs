@@ -14,96 +14,858 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::coordinator_interface::{FoundryModule, Port};
-use crate::module::UserModule;
+use crate::config::{ModuleConfig, ModuleEvent, PanicHook, RtoConfigHook};
+use crate::coordinator_interface::{
+    FoundryModule, LinkHealth, LinkStatus, ModuleMetrics, ModuleRuntimeConfig, ModuleState, PoolStats, Port, PortInfo,
+    PortStats, ShutdownOutcome,
+};
+use crate::error::{ExportError, ModuleError, PoolError};
+use crate::module::{ThreadPoolHandle, UserModule};
+use crate::panic_hook::{self, module_worker_name, port_worker_name};
 use crate::port::ModulePort;
+use crate::trace::Span;
 use crossbeam::channel;
 use fproc_sndbx::ipc::Ipc;
 use parking_lot::{Mutex, RwLock};
 use remote_trait_object::raw_exchange::Skeleton;
 use remote_trait_object::{Config as RtoConfig, Service, ServiceRef, ServiceToExport};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use threadpool::ThreadPool;
 
+/// How long the shutdown waits for the serving thread to take its signal
+const SHUTDOWN_SIGNAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The signal to stop serving, carrying the sender to acknowledge it with
+type ShutdownSignal = channel::Sender<channel::Sender<()>>;
+
+enum Slot {
+    Prepared(Skeleton),
+    /// Waiting for the first export in the lazy mode, with the constructor argument
+    Pending(Vec<u8>),
+    Failed,
+}
+
+/// The services to be exported, shared by the ports behind a `RwLock`.
+///
+/// Exports only read the pool, so the ports export in parallel, while `load` and `clear` write it.
+/// Each slot has its own lock instead, so that a lazy service is prepared only once.
 pub struct ExportingServicePool {
-    pool: Vec<Option<Skeleton>>,
+    slots: Vec<Mutex<Slot>>,
+    /// Constructor name of each entry
+    names: Vec<String>,
+    /// Key of each entry, which is unique in the pool
+    keys: Vec<String>,
+    /// Whether each entry has been exported by any port
+    exported: Vec<AtomicBool>,
+    /// Whether to defer preparing each service until it is exported for the first time
+    lazy: bool,
+    cleared: bool,
 }
 
 impl ExportingServicePool {
-    pub fn new() -> Self {
+    pub fn new(lazy: bool) -> Self {
         Self {
-            pool: Vec::new(),
+            slots: Vec::new(),
+            names: Vec::new(),
+            keys: Vec::new(),
+            exported: Vec::new(),
+            lazy,
+            cleared: false,
+        }
+    }
+
+    /// Prepares all the services, returning the errors of the entries that have failed.
+    ///
+    /// Nothing is prepared here in the lazy mode.
+    pub fn load(&mut self, ctors: &[(String, String, Vec<u8>)], module: &mut impl UserModule) -> Vec<ExportError> {
+        let lazy = self.lazy;
+        let mut errors = Vec::new();
+        self.slots = ctors
+            .iter()
+            .enumerate()
+            .map(|(index, (_, ctor_name, arg))| {
+                if lazy {
+                    return Mutex::new(Slot::Pending(arg.clone()))
+                }
+                Mutex::new(match prepare(module, index, ctor_name, arg) {
+                    Ok(skeleton) => Slot::Prepared(skeleton),
+                    Err(error) => {
+                        errors.push(error);
+                        Slot::Failed
+                    }
+                })
+            })
+            .collect();
+        self.names = ctors.iter().map(|(_, ctor_name, _)| ctor_name.clone()).collect();
+        self.keys = ctors.iter().map(|(key, ..)| key.clone()).collect();
+        self.exported = ctors.iter().map(|_| AtomicBool::new(false)).collect();
+        self.cleared = false;
+        errors
+    }
+
+    /// Looks up the service, preparing it with `module` if it is still pending.
+    ///
+    /// The same index can be exported any number of times until the pool is cleared,
+    /// and every export shares the one service object, which lives as long as any of them does.
+    pub fn export<T: UserModule>(&self, index: usize, module: &Weak<Mutex<T>>) -> Result<Skeleton, PoolError> {
+        if self.cleared {
+            return Err(PoolError::AlreadyCleared)
+        }
+        let len = self.slots.len();
+        let mut slot = self
+            .slots
+            .get(index)
+            .ok_or(PoolError::IndexOutOfRange {
+                index,
+                len,
+            })?
+            .lock();
+        if let Slot::Pending(ctor_arg) = &*slot {
+            let module = module.upgrade().ok_or(PoolError::ModuleGone)?;
+            let result = prepare(&mut *module.lock(), index, &self.names[index], ctor_arg);
+            match result {
+                Ok(skeleton) => *slot = Slot::Prepared(skeleton),
+                Err(error) => {
+                    *slot = Slot::Failed;
+                    return Err(PoolError::PreparationFailed {
+                        error,
+                    })
+                }
+            }
+        }
+        match &*slot {
+            Slot::Prepared(skeleton) => {
+                self.exported[index].store(true, Ordering::Relaxed);
+                Ok(skeleton.clone())
+            }
+            Slot::Failed => Err(PoolError::NotPrepared {
+                index,
+            }),
+            Slot::Pending(_) => unreachable!(),
+        }
+    }
+
+    /// Looks up the service by the constructor name, which must be unique in the pool.
+    pub fn export_by_name<T: UserModule>(&self, name: &str, module: &Weak<Mutex<T>>) -> Result<Skeleton, PoolError> {
+        if self.cleared {
+            return Err(PoolError::AlreadyCleared)
+        }
+        let mut indices = self.names.iter().enumerate().filter(|(_, ctor_name)| *ctor_name == name).map(|(i, _)| i);
+        match (indices.next(), indices.next()) {
+            (Some(index), None) => self.export(index, module),
+            (None, _) => Err(PoolError::UnknownName {
+                name: name.to_owned(),
+            }),
+            (Some(_), Some(_)) => Err(PoolError::AmbiguousName {
+                name: name.to_owned(),
+            }),
+        }
+    }
+
+    /// Looks up the service by the key given to the initialization.
+    pub fn export_by_key<T: UserModule>(&self, key: &str, module: &Weak<Mutex<T>>) -> Result<Skeleton, PoolError> {
+        if self.cleared {
+            return Err(PoolError::AlreadyCleared)
+        }
+        let index = self.keys.iter().position(|k| k == key).ok_or_else(|| PoolError::UnknownKey {
+            key: key.to_owned(),
+        })?;
+        self.export(index, module)
+    }
+
+    /// Number of the entries loaded, including the ones that have failed to be prepared
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Indices of all the entries loaded, in order
+    pub fn indices(&self) -> Result<Vec<usize>, PoolError> {
+        if self.cleared {
+            return Err(PoolError::AlreadyCleared)
         }
+        Ok((0..self.slots.len()).collect())
     }
 
-    pub fn load(&mut self, ctors: &[(String, Vec<u8>)], module: &mut impl UserModule) {
-        self.pool = ctors.iter().map(|(method, arg)| Some(module.prepare_service_to_export(method, arg))).collect();
+    pub fn ctor_names(&self) -> Vec<String> {
+        self.names.clone()
     }
 
-    pub fn export(&mut self, index: usize) -> Skeleton {
-        self.pool[index].as_ref().unwrap().clone()
+    /// Keys of the entries that no port has exported, including the ones that have failed to be prepared
+    pub fn unexported_keys(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .zip(&self.exported)
+            .filter(|(_, exported)| !exported.load(Ordering::Relaxed))
+            .map(|(key, _)| key.clone())
+            .collect()
     }
 
     pub fn clear(&mut self) {
-        self.pool.clear();
+        self.slots.clear();
+        self.names.clear();
+        self.keys.clear();
+        self.exported.clear();
+        self.cleared = true;
     }
 }
 
-struct ModuleContext<T: UserModule> {
+/// Keys the positional exports by their indices.
+fn keyed_by_index(exports: &[(String, Vec<u8>)]) -> Vec<(String, String, Vec<u8>)> {
+    exports
+        .iter()
+        .enumerate()
+        .map(|(index, (ctor_name, arg))| (index.to_string(), ctor_name.clone(), arg.clone()))
+        .collect()
+}
+
+fn prepare(
+    module: &mut impl UserModule,
+    index: usize,
+    ctor_name: &str,
+    ctor_arg: &[u8],
+) -> Result<Skeleton, ExportError> {
+    module.prepare_service_to_export(ctor_name, ctor_arg).map_err(|err| ExportError {
+        index,
+        ctor_name: ctor_name.to_owned(),
+        message: err.to_string(),
+    })
+}
+
+/// Runs a call into the user module, converting a panic into [`ModuleError::Panicked`].
+///
+/// The lock of the user module is released while unwinding, so the module stays usable.
+fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, ModuleError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| ModuleError::Panicked {
+        message: panic_message(&*payload),
+    })
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic payload".to_owned()
+    }
+}
+
+/// Name of the module given by the config, or the type name of the user module.
+fn module_name<T: UserModule>(config: &ModuleConfig) -> String {
+    config.name.clone().unwrap_or_else(|| std::any::type_name::<T>().to_owned())
+}
+
+struct ModuleContext<T: UserModule + 'static> {
     user_context: Option<Arc<Mutex<T>>>,
-    exporting_service_pool: Arc<Mutex<ExportingServicePool>>,
+    exporting_service_pool: Arc<RwLock<ExportingServicePool>>,
+    /// Constructors given at the initialization along with their keys, kept to load a pool for each dynamic port
+    exports: Vec<(String, String, Vec<u8>)>,
     ports: HashMap<String, Arc<RwLock<ModulePort<T>>>>,
     thread_pool: Arc<Mutex<ThreadPool>>,
+    /// Whether `thread_pool` is given by [`create_foundry_module_with_pool`], so that it must not be resized
+    shared_thread_pool: bool,
+    /// Size of the pool that each port gets for itself, instead of sharing `thread_pool`
+    per_port_threads: Option<usize>,
+    /// Whether to keep `exporting_service_pool` after the bootstrap, for the dynamic ports to share
+    retain_exports: bool,
+    /// Whether the bootstrap fails if it would clear the services that no port has exported
+    strict_exports: bool,
+    rto_config_hook: Option<RtoConfigHook>,
+    /// Given by [`ModuleRuntimeConfig::call_timeout`] for the ports created after the initialization
+    default_call_timeout: Option<Duration>,
+    /// Runtime that drives the asynchronous calls
+    #[cfg(feature = "tokio")]
+    runtime: Option<tokio::runtime::Handle>,
     bootstrap_finished: bool,
+    /// When the module has been created, for the uptime
+    created_at: Instant,
+    /// Counter of the heartbeats, kept apart from the user module so that it never waits for a call into it
+    heartbeat: AtomicU64,
+    /// Name of the module given by the config, or the type name of the user module
+    module_name: String,
+    /// Unique to the module, which names its workers along with `module_name`
+    module_id: u64,
+    /// Given by [`ModuleConfig::panic_hook`], to be registered again by `reinitialize`
+    panic_hook: Option<PanicHook>,
+    /// Id of `panic_hook` registered until the shutdown
+    panic_hook_id: Option<u64>,
+    events: Option<channel::Sender<ModuleEvent>>,
 
     /// This is only for the case created by [`start()`].
-    shutdown_signal: channel::Sender<()>,
+    ///
+    /// It is taken by the first shutdown, so that the next ones can be no-op.
+    shutdown_signal: Option<ShutdownSignal>,
+    /// The parent of the spans of the ports
+    span: Span,
 }
 
-impl<T: UserModule> Service for ModuleContext<T> {}
+impl<T: UserModule + 'static> ModuleContext<T> {
+    /// Makes the module ready to be initialized, on its creation and again by `reinitialize` after a shutdown.
+    fn set_up(&mut self, shutdown_signal: ShutdownSignal) {
+        self.shutdown_signal = Some(shutdown_signal);
+        self.ports.clear();
+        self.bootstrap_finished = false;
+        if let Some(hook) = self.panic_hook.clone() {
+            self.panic_hook_id = Some(panic_hook::register(hook, self.module_name.clone(), self.module_id));
+        }
+    }
+
+    /// Returns the user module, which exists only from the initialization until the shutdown.
+    fn user_context(&self) -> Result<&Arc<Mutex<T>>, ModuleError> {
+        match &self.user_context {
+            Some(user_context) => Ok(user_context),
+            None if self.shutdown_signal.is_none() => Err(ModuleError::ShutDown),
+            None => Err(ModuleError::NotInitialized),
+        }
+    }
 
-impl<T: UserModule + 'static> FoundryModule for ModuleContext<T> {
-    fn initialize(&mut self, arg: &[u8], exports: &[(String, Vec<u8>)]) {
-        assert!(self.user_context.is_none(), "Moudle has been initialized twice");
-        let mut module = T::new(arg);
-        self.exporting_service_pool.lock().load(&exports, &mut module);
-        self.user_context.replace(Arc::new(Mutex::new(module)));
+    fn emit(&self, event: ModuleEvent) {
+        if let Some(events) = &self.events {
+            // Nobody may be listening anymore.
+            let _ = events.send(event);
+        }
     }
 
-    fn create_port(&mut self, name: &str) -> ServiceRef<dyn Port> {
-        assert!(!self.bootstrap_finished);
+    fn add_port(
+        &mut self,
+        name: &str,
+        exporting_service_pool: Arc<RwLock<ExportingServicePool>>,
+    ) -> Result<ServiceRef<dyn Port>, ModuleError> {
+        let _enter = self.span.enter();
+        event!(port = name, "create_port");
+        let user_context = Arc::clone(self.user_context()?);
+        self.remove_disconnected_ports();
+        if self.ports.contains_key(name) {
+            return Err(ModuleError::DuplicatePort {
+                name: name.to_owned(),
+            })
+        }
+        let thread_pool = match self.per_port_threads {
+            Some(threads) => Arc::new(Mutex::new(ThreadPool::with_name(
+                port_worker_name(&self.module_name, self.module_id, name),
+                threads,
+            ))),
+            None => Arc::clone(&self.thread_pool),
+        };
         let port = Arc::new(RwLock::new(ModulePort::new(
-            Arc::downgrade(self.user_context.as_ref().unwrap()),
-            Arc::clone(&self.thread_pool),
-            Arc::clone(&self.exporting_service_pool),
+            name.to_owned(),
+            Arc::downgrade(&user_context),
+            thread_pool,
+            exporting_service_pool,
+            self.default_call_timeout,
+            self.rto_config_hook.clone(),
+            span!(parent: &self.span, "port", port = name),
         )));
-        let port_ = Arc::clone(&port);
-        assert!(self.ports.insert(name.to_owned(), port).is_none());
-        ServiceRef::create_export(port_ as Arc<RwLock<dyn Port>>)
+        self.ports.insert(name.to_owned(), Arc::clone(&port));
+        user_context.lock().port_created(name);
+        self.emit(ModuleEvent::PortCreated {
+            name: name.to_owned(),
+        });
+        Ok(ServiceRef::create_export(port as Arc<RwLock<dyn Port>>))
+    }
+
+    /// Reports the first of the messages that the ports have failed to send for their size.
+    fn take_oversized(&self) -> Result<(), ModuleError> {
+        for port in self.ports.values() {
+            port.read().take_oversized()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the panic of a call with the message that has failed it, if any.
+    ///
+    /// A call through a link panics when the transport rejects its message for the size.
+    fn oversized_over_panic(&self, result: Result<Vec<u8>, ModuleError>) -> Result<Vec<u8>, ModuleError> {
+        if let Err(ModuleError::Panicked {
+            ..
+        }) = result
+        {
+            self.take_oversized()?;
+        }
+        result
+    }
+
+    /// Passes the call to the user module, running it on the runtime if it is asynchronous.
+    fn call_user_module(&self, user_context: &Arc<Mutex<T>>, method: &str, arg: &[u8]) -> Result<Vec<u8>, ModuleError> {
+        #[cfg(feature = "tokio")]
+        {
+            if let Some(runtime) = &self.runtime {
+                // The module is released before the future runs, so that other calls are served meanwhile.
+                let future = catch_panic(|| user_context.lock().call_async(method, arg))?;
+                if let Some(future) = future {
+                    // The future is driven by the workers of the runtime, even if this thread belongs to it.
+                    let (sender, receiver) = channel::bounded(1);
+                    let task = runtime.spawn(future);
+                    runtime.spawn(async move {
+                        let _ = sender.send(task.await);
+                    });
+                    return match receiver.recv() {
+                        Ok(Ok(result)) => result,
+                        Ok(Err(error)) if error.is_panic() => Err(ModuleError::Panicked {
+                            message: panic_message(&*error.into_panic()),
+                        }),
+                        // The runtime has dropped the task without finishing it.
+                        _ => Err(ModuleError::CallCancelled),
+                    }
+                }
+            }
+        }
+        catch_panic(|| user_context.lock().call(method, arg))?
+    }
+
+    /// Removes the ports that have been disconnected through [`Port::disconnect`].
+    fn remove_disconnected_ports(&mut self) {
+        self.ports.retain(|_, port| !port.read().is_disconnected());
+    }
+
+    /// Asks the user module whether it can be shut down, unless it has been shut down already.
+    fn rejects_shutdown(&self) -> bool {
+        self.shutdown_signal.is_some()
+            && self.user_context.as_ref().map_or(false, |user_context| !user_context.lock().can_shutdown())
+    }
+
+    /// Shuts down the module, waiting for the ports to be torn down within `timeout` if given.
+    ///
+    /// The module is shut down even if the serving thread doesn't take the signal, which is returned as an error
+    /// along with the outcome.
+    fn tear_down(&mut self, timeout: Option<Duration>) -> (ShutdownOutcome, Result<(), ModuleError>) {
+        let shutdown_signal = match self.shutdown_signal.take() {
+            Some(shutdown_signal) => shutdown_signal,
+            None => return (ShutdownOutcome::Clean, Ok(())),
+        };
+        self.remove_disconnected_ports();
+        let _enter = self.span.enter();
+        event!(ports = self.ports.len(), "shutdown");
+        // Such a port has no link to tear down, as the bootstrap has been aborted before initializing it.
+        self.ports.retain(|name, port| {
+            let initialized = port.read().is_initialized();
+            if !initialized {
+                log::warn!("Port {} is shut down without having been initialized", name);
+            }
+            initialized
+        });
+
+        let mut undrained_ports = Vec::new();
+        // It is missing if the initialization has failed.
+        if let Some(user_context) = self.user_context.take() {
+            user_context.lock().shutting_down();
+
+            // Important: We have to disable GC for **ALL** ports first, and then clear one by one.
+            for port in self.ports.values() {
+                port.write().get_rto_context().disable_garbage_collection();
+            }
+            // The calls being served must not see the services torn down under them.
+            // The pools are cloned so that their locks are not held, in case a call needs another worker to finish.
+            let mut thread_pools = vec![self.thread_pool.lock().clone()];
+            thread_pools.extend(self.ports.values().map(|port| port.read().thread_pool().lock().clone()));
+            match timeout {
+                None => {
+                    for thread_pool in &thread_pools {
+                        thread_pool.join();
+                    }
+                    for port in self.ports.values() {
+                        port.write().get_rto_context().clear_service_registry();
+                    }
+                }
+                Some(timeout) => {
+                    let deadline = Instant::now() + timeout;
+                    if !drain_within(thread_pools, deadline) {
+                        log::warn!("Calls in flight have not finished within {:?}", timeout);
+                    }
+                    undrained_ports = clear_ports_within(&self.ports, deadline);
+                }
+            }
+        }
+        self.ports.clear();
+        if let Some(panic_hook_id) = self.panic_hook_id.take() {
+            panic_hook::deregister(panic_hook_id);
+        }
+        self.emit(ModuleEvent::ShutDown);
+        let signaled = signal_shutdown(&shutdown_signal);
+
+        if undrained_ports.is_empty() {
+            (ShutdownOutcome::Clean, signaled)
+        } else {
+            (
+                ShutdownOutcome::Forced {
+                    undrained_ports,
+                },
+                signaled,
+            )
+        }
+    }
+}
+
+impl<T: UserModule + 'static> Service for ModuleContext<T> {}
+
+impl<T: UserModule + 'static> Drop for ModuleContext<T> {
+    fn drop(&mut self) {
+        // The ports must be torn down in order, even if the coordinator has dropped the module without shutting it down.
+        // It does nothing if the module has been shut down already.
+        if let (_, Err(err)) = self.tear_down(None) {
+            log::error!("{}", err);
+        }
+    }
+}
+
+/// Tells the serving thread to stop, waiting for it to take the signal within [`SHUTDOWN_SIGNAL_TIMEOUT`].
+fn signal_shutdown(shutdown_signal: &ShutdownSignal) -> Result<(), ModuleError> {
+    let (taken_send, taken_recv) = channel::bounded(1);
+    match shutdown_signal.try_send(taken_send) {
+        Ok(()) => {}
+        // Nobody serves the module in case of `create_foundry_module()`.
+        Err(channel::TrySendError::Disconnected(_)) => return Ok(()),
+        Err(channel::TrySendError::Full(_)) => unreachable!("The shutdown signal is sent only once"),
+    }
+    taken_recv.recv_timeout(SHUTDOWN_SIGNAL_TIMEOUT).map_err(|_| ModuleError::ShutdownSignalTimeout {
+        timeout: SHUTDOWN_SIGNAL_TIMEOUT,
+    })
+}
+
+/// Waits for the jobs of the thread pools to finish until `deadline`, returning whether they have finished.
+fn drain_within(thread_pools: Vec<ThreadPool>, deadline: Instant) -> bool {
+    let (drained_send, drained_recv) = channel::bounded(1);
+    std::thread::spawn(move || {
+        for thread_pool in &thread_pools {
+            thread_pool.join();
+        }
+        let _ = drained_send.send(());
+    });
+    drained_recv.recv_timeout(deadline.saturating_duration_since(Instant::now())).is_ok()
+}
+
+/// Clears the service registries of the ports in parallel, returning the names of the ones that haven't finished by `deadline`.
+///
+/// The threads of the unfinished ones are left running.
+fn clear_ports_within<T: UserModule + 'static>(
+    ports: &HashMap<String, Arc<RwLock<ModulePort<T>>>>,
+    deadline: Instant,
+) -> Vec<String> {
+    let (cleared_send, cleared_recv) = channel::unbounded();
+    for (name, port) in ports {
+        let name = name.clone();
+        let port = Arc::clone(port);
+        let cleared_send = cleared_send.clone();
+        std::thread::spawn(move || {
+            port.write().get_rto_context().clear_service_registry();
+            let _ = cleared_send.send(name);
+        });
+    }
+
+    let mut undrained: HashSet<&String> = ports.keys().collect();
+    while !undrained.is_empty() {
+        match cleared_recv.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(name) => {
+                undrained.remove(&name);
+            }
+            Err(_) => break,
+        }
+    }
+    let mut undrained: Vec<String> = undrained.into_iter().cloned().collect();
+    undrained.sort();
+    for name in &undrained {
+        log::warn!("Port {} has not been torn down in time", name);
+    }
+    undrained
+}
+
+impl<T: UserModule + 'static> FoundryModule for ModuleContext<T> {
+    fn initialize(&mut self, arg: &[u8], exports: &[(String, Vec<u8>)]) -> Result<(), ModuleError> {
+        self.initialize_with_keys(arg, &keyed_by_index(exports))
+    }
+
+    fn initialize_with_keys(&mut self, arg: &[u8], exports: &[(String, String, Vec<u8>)]) -> Result<(), ModuleError> {
+        let _enter = self.span.enter();
+        event!(exports = exports.len(), "initialize");
+        if self.user_context.is_some() {
+            return Err(ModuleError::AlreadyInitialized)
+        }
+        let mut keys = HashSet::new();
+        if let Some((key, ..)) = exports.iter().find(|(key, ..)| !keys.insert(key)) {
+            return Err(ModuleError::DuplicateExportKey {
+                key: key.clone(),
+            })
+        }
+        let mut module = T::new(arg).map_err(|err| ModuleError::InitializationFailed {
+            message: err.to_string(),
+        })?;
+        let errors = {
+            let mut pool = self.exporting_service_pool.write();
+            let errors = pool.load(&exports, &mut module);
+            if !pool.is_empty() {
+                log::debug!("Loaded {} exports: {:?}", pool.len(), pool.ctor_names());
+            }
+            errors
+        };
+        self.user_context.replace(Arc::new(Mutex::new(module)));
+        self.exports = exports.to_vec();
+        self.emit(ModuleEvent::Initialized);
+        if !errors.is_empty() {
+            return Err(ModuleError::ExportPreparationFailed {
+                errors,
+            })
+        }
+        Ok(())
+    }
+
+    fn initialize_with_runtime_config(
+        &mut self,
+        arg: &[u8],
+        exports: &[(String, String, Vec<u8>)],
+        runtime_config: ModuleRuntimeConfig,
+    ) -> Result<(), ModuleError> {
+        if self.user_context.is_some() {
+            return Err(ModuleError::AlreadyInitialized)
+        }
+        if runtime_config.worker_threads == Some(0) {
+            return Err(ModuleError::InitializationFailed {
+                message: "The thread pool needs at least one worker".to_owned(),
+            })
+        }
+        if runtime_config.worker_threads.is_some() && self.shared_thread_pool {
+            return Err(ModuleError::InitializationFailed {
+                message: "The thread pool is shared with other modules, so it can't be resized".to_owned(),
+            })
+        }
+        let result = self.initialize_with_keys(arg, exports);
+        // The module is initialized even if some of its exports have failed, and only then is the runtime changed.
+        if self.user_context.is_some() {
+            if let Some(threads) = runtime_config.worker_threads {
+                self.thread_pool.lock().set_num_threads(threads);
+            }
+            self.default_call_timeout = runtime_config.call_timeout;
+        }
+        result
+    }
+
+    fn create_port(&mut self, name: &str) -> Result<ServiceRef<dyn Port>, ModuleError> {
+        if self.bootstrap_finished {
+            return Err(ModuleError::BootstrapAlreadyFinished)
+        }
+        let exporting_service_pool = Arc::clone(&self.exporting_service_pool);
+        self.add_port(name, exporting_service_pool)
+    }
+
+    fn create_ports(&mut self, names: &[String]) -> Vec<Result<ServiceRef<dyn Port>, ModuleError>> {
+        names.iter().map(|name| self.create_port(name)).collect()
+    }
+
+    fn create_dynamic_port(&mut self, name: &str) -> Result<ServiceRef<dyn Port>, ModuleError> {
+        // The shutdown leaves the bootstrap finished.
+        let user_context = Arc::clone(self.user_context()?);
+        if !self.bootstrap_finished {
+            return Err(ModuleError::BootstrapNotFinished)
+        }
+        if self.retain_exports {
+            let exporting_service_pool = Arc::clone(&self.exporting_service_pool);
+            return self.add_port(name, exporting_service_pool)
+        }
+        // Only the services that the new link exports are prepared.
+        let mut exporting_service_pool = ExportingServicePool::new(true);
+        exporting_service_pool.load(&self.exports, &mut *user_context.lock());
+        self.add_port(name, Arc::new(RwLock::new(exporting_service_pool)))
     }
 
-    fn finish_bootstrap(&mut self) {
-        self.exporting_service_pool.lock().clear();
-        assert!(!self.bootstrap_finished);
+    fn finish_bootstrap(&mut self) -> Result<(), ModuleError> {
+        let user_context = Arc::clone(self.user_context()?);
+        if self.bootstrap_finished {
+            return Err(ModuleError::BootstrapAlreadyFinished)
+        }
+        self.remove_disconnected_ports();
+        let _enter = self.span.enter();
+        event!(ports = self.ports.len(), "finish_bootstrap");
+        if let Some((name, _)) = self.ports.iter().find(|(_, port)| !port.read().is_initialized()) {
+            return Err(ModuleError::PortNotInitialized {
+                name: name.clone(),
+            })
+        }
+        if !self.retain_exports {
+            let unexported = self.exporting_service_pool.read().unexported_keys();
+            if !unexported.is_empty() {
+                if self.strict_exports {
+                    return Err(ModuleError::UnexportedServices {
+                        keys: unexported,
+                    })
+                }
+                log::warn!("Clearing {} service(s) that have never been exported: {:?}", unexported.len(), unexported);
+            }
+            self.exporting_service_pool.write().clear();
+        }
         self.bootstrap_finished = true;
+        let thread_pool = ThreadPoolHandle::new(Arc::clone(&self.thread_pool));
+        user_context.lock().bootstrap_finished(thread_pool);
+        self.emit(ModuleEvent::BootstrapFinished);
+        Ok(())
     }
 
-    fn debug(&mut self, arg: &[u8]) -> Vec<u8> {
-        self.user_context.as_ref().unwrap().lock().debug(arg)
+    fn check_links(&self) -> Result<Vec<LinkHealth>, ModuleError> {
+        let mut user_context = self.user_context()?.lock();
+        Ok(user_context
+            .ping_imports()
+            .into_iter()
+            .map(|(module, ping)| {
+                let status = match panic::catch_unwind(AssertUnwindSafe(ping)) {
+                    Ok(()) => LinkStatus::Alive,
+                    Err(payload) => LinkStatus::Dead {
+                        message: panic_message(&*payload),
+                    },
+                };
+                LinkHealth {
+                    module,
+                    status,
+                }
+            })
+            .collect())
     }
 
-    fn shutdown(&mut self) {
-        // Important: We have to disable GC for **ALL** ports first, and then clear one by one.
-        for port in self.ports.values() {
-            port.write().get_rto_context().disable_garbage_collection();
+    fn heartbeat(&self) -> u64 {
+        self.heartbeat.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn debug(&self, arg: &[u8]) -> Result<Vec<u8>, ModuleError> {
+        let user_context = self.user_context()?;
+        self.take_oversized()?;
+        let result = catch_panic(|| user_context.lock().debug(arg));
+        self.oversized_over_panic(result)
+    }
+
+    fn call(&self, method: &str, arg: &[u8]) -> Result<Vec<u8>, ModuleError> {
+        let user_context = self.user_context()?;
+        self.take_oversized()?;
+        let result = self.call_user_module(user_context, method, arg);
+        self.oversized_over_panic(result)
+    }
+
+    fn metrics(&self) -> ModuleMetrics {
+        let ports: BTreeMap<String, PortStats> = self
+            .ports
+            .iter()
+            .map(|(name, port)| (name, port.read()))
+            .filter(|(_, port)| !port.is_disconnected())
+            .map(|(name, port)| (name.clone(), port.stats()))
+            .collect();
+        ModuleMetrics {
+            uptime: self.created_at.elapsed(),
+            exported_count: ports.values().map(|stats| stats.exported).sum(),
+            imported_count: ports.values().map(|stats| stats.imported).sum(),
+            ports,
         }
+    }
+
+    fn inflight_calls(&self) -> usize {
+        // The ports without their own pools share the one of the module.
+        let mut thread_pools = vec![Arc::clone(&self.thread_pool)];
         for port in self.ports.values() {
-            port.write().get_rto_context().clear_service_registry();
+            let thread_pool = port.read().thread_pool();
+            if !thread_pools.iter().any(|known| Arc::ptr_eq(known, &thread_pool)) {
+                thread_pools.push(thread_pool);
+            }
         }
-        self.user_context.take().unwrap();
-        self.ports.clear();
-        self.shutdown_signal.send(()).unwrap();
+        thread_pools
+            .iter()
+            .map(|thread_pool| {
+                let thread_pool = thread_pool.lock();
+                thread_pool.active_count() + thread_pool.queued_count()
+            })
+            .sum()
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        let thread_pool = self.thread_pool.lock();
+        PoolStats {
+            size: thread_pool.max_count(),
+            active: thread_pool.active_count(),
+            queued: thread_pool.queued_count(),
+        }
+    }
+
+    fn exported_count(&self) -> usize {
+        self.exporting_service_pool.read().len()
+    }
+
+    fn imported_count(&self) -> usize {
+        self.user_context.as_ref().map_or(0, |user_context| user_context.lock().imported_count())
+    }
+
+    fn imported_names(&self) -> Vec<String> {
+        self.user_context.as_ref().map_or_else(Vec::new, |user_context| user_context.lock().imported_names())
+    }
+
+    fn retained_proxy_count(&self) -> usize {
+        self.user_context.as_ref().map_or(0, |user_context| user_context.lock().retained_proxy_count())
+    }
+
+    fn state(&self) -> ModuleState {
+        if self.shutdown_signal.is_none() {
+            ModuleState::ShutDown
+        } else if self.user_context.is_none() {
+            ModuleState::Uninitialized
+        } else if self.bootstrap_finished {
+            ModuleState::Bootstrapped
+        } else {
+            ModuleState::Initialized
+        }
+    }
+
+    fn list_ports(&self) -> Vec<PortInfo> {
+        let mut ports: Vec<PortInfo> = self
+            .ports
+            .iter()
+            .map(|(name, port)| (name, port.read()))
+            .filter(|(_, port)| !port.is_disconnected())
+            .map(|(name, port)| PortInfo {
+                name: name.clone(),
+                connected_module: port.connected_module_name().to_owned(),
+                initialized: port.is_initialized(),
+                rto_name: port.rto_name().map(ToOwned::to_owned),
+            })
+            .collect();
+        ports.sort_by(|a, b| a.name.cmp(&b.name));
+        ports
+    }
+
+    fn shutdown(&mut self) -> Result<(), ModuleError> {
+        if self.rejects_shutdown() {
+            return Err(ModuleError::ShutdownRejected)
+        }
+        self.tear_down(None).1
+    }
+
+    fn force_shutdown(&mut self) {
+        if let (_, Err(err)) = self.tear_down(None) {
+            log::error!("{}", err);
+        }
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> ShutdownOutcome {
+        if self.rejects_shutdown() {
+            return ShutdownOutcome::Rejected
+        }
+        let (outcome, signaled) = self.tear_down(Some(timeout));
+        if let Err(err) = signaled {
+            log::error!("{}", err);
+        }
+        outcome
+    }
+
+    fn reinitialize(&mut self, arg: &[u8], exports: &[(String, Vec<u8>)]) -> Result<(), ModuleError> {
+        if self.shutdown_signal.is_some() {
+            return Err(ModuleError::NotShutDown)
+        }
+        // Nobody waits for the signal anymore, as the module is not served by `start()`.
+        let (shutdown_signal, _) = channel::bounded(1);
+        self.set_up(shutdown_signal);
+        // The pool is reloaded by the initialization.
+        self.initialize(arg, exports)
     }
 }
 
@@ -112,23 +874,84 @@ impl<T: UserModule + 'static> FoundryModule for ModuleContext<T> {
 /// This is useful when you want to realize linkability without any execution or RTO connection.
 /// If you're writing a plain module, this is not for you because your job is writing an executable that runs [`FoundryModule`],
 /// not obtaining the actual instance of [`FoundryModule`].
-pub fn create_foundry_module<T: UserModule + 'static>(
-    mut module: T,
+///
+/// Entries of `exports` that fail to be prepared are not reported here, but will fail on export.
+pub fn create_foundry_module<T: UserModule + 'static>(module: T, exports: &[(String, Vec<u8>)]) -> impl FoundryModule {
+    create_foundry_module_with_config(module, exports, ModuleConfig::default())
+}
+
+/// Same as [`create_foundry_module`], but with the given configuration.
+pub fn create_foundry_module_with_config<T: UserModule + 'static>(
+    module: T,
+    exports: &[(String, Vec<u8>)],
+    config: ModuleConfig,
+) -> impl FoundryModule {
+    build_foundry_module(module, exports, config, None)
+}
+
+/// Same as [`create_foundry_module`], but serves the calls on the given pool, which can be shared by many modules.
+///
+/// It saves the threads of the modules that are mostly idle, at the cost of their isolation;
+/// a module busy with slow calls leaves the others waiting for a worker,
+/// and the shutdown of a module waits for the calls of all the modules sharing the pool.
+/// For the same reason, [`ModuleRuntimeConfig::worker_threads`] is rejected
+/// when the module is initialized again after its shutdown.
+///
+/// [`ModuleRuntimeConfig::worker_threads`]: ./coordinator_interface/struct.ModuleRuntimeConfig.html#structfield.worker_threads
+pub fn create_foundry_module_with_pool<T: UserModule + 'static>(
+    module: T,
     exports: &[(String, Vec<u8>)],
+    thread_pool: Arc<Mutex<ThreadPool>>,
 ) -> impl FoundryModule {
+    build_foundry_module(module, exports, ModuleConfig::default(), Some(thread_pool))
+}
+
+/// Creates an initialized module, with a pool of its own unless `shared_thread_pool` is given.
+fn build_foundry_module<T: UserModule + 'static>(
+    mut module: T,
+    exports: &[(String, Vec<u8>)],
+    config: ModuleConfig,
+    shared_thread_pool: Option<Arc<Mutex<ThreadPool>>>,
+) -> ModuleContext<T> {
     let (shutdown_signal, _) = channel::bounded(1);
-    let exporting_service_pool = Arc::new(Mutex::new(ExportingServicePool::new()));
-    exporting_service_pool.lock().load(&exports, &mut module);
+    let module_name = module_name::<T>(&config);
+    let module_id = panic_hook::next_module_id();
+    let shared = shared_thread_pool.is_some();
+    let thread_pool = shared_thread_pool.unwrap_or_else(|| {
+        Arc::new(Mutex::new(ThreadPool::with_name(module_worker_name(&module_name, module_id), config.worker_threads)))
+    });
+    let exporting_service_pool = Arc::new(RwLock::new(ExportingServicePool::new(config.lazy_exports)));
+    let exports = keyed_by_index(exports);
+    let _ = exporting_service_pool.write().load(&exports, &mut module);
 
-    ModuleContext::<T> {
+    let mut context = ModuleContext::<T> {
         user_context: Some(Arc::new(Mutex::new(module))),
         exporting_service_pool,
+        exports,
         ports: HashMap::new(),
-        // TODO: decide thread pool size from the configuration
-        thread_pool: Arc::new(Mutex::new(ThreadPool::new(16))),
-        shutdown_signal,
+        thread_pool,
+        shared_thread_pool: shared,
+        per_port_threads: config.per_port_threads,
+        retain_exports: config.retain_exports,
+        strict_exports: config.strict_exports,
+        rto_config_hook: config.rto_config_hook,
+        default_call_timeout: None,
+        #[cfg(feature = "tokio")]
+        runtime: config.runtime.clone(),
+        shutdown_signal: None,
         bootstrap_finished: false,
-    }
+        created_at: Instant::now(),
+        heartbeat: AtomicU64::new(0),
+        module_name,
+        module_id,
+        panic_hook: config.panic_hook,
+        panic_hook_id: None,
+        events: config.events,
+        span: span!("module", module = std::any::type_name::<T>()),
+    };
+    context.set_up(shutdown_signal);
+    context.emit(ModuleEvent::Initialized);
+    context
 }
 
 /// A function that runs a module.
@@ -138,27 +961,154 @@ pub fn create_foundry_module<T: UserModule + 'static>(
 ///
 /// This function will not return until Foundry host is shutdown.
 pub fn start<I: Ipc + 'static, T: UserModule + 'static>(args: Vec<String>) {
-    let (shutdown_signal, shutdown_wait) = channel::bounded(0);
-    let mut executee = fproc_sndbx::execution::executee::start::<I>(args);
-    let module = Box::new(ModuleContext::<T> {
+    start_with_config::<I, T>(args, ModuleConfig::default())
+}
+
+/// Same as [`start`], but with the given configuration.
+pub fn start_with_config<I: Ipc + 'static, T: UserModule + 'static>(args: Vec<String>, config: ModuleConfig) {
+    let (module, shutdown_wait) = create_module_context::<T>(&config);
+    serve::<I>(args, ServiceToExport::new(Box::new(module) as Box<dyn FoundryModule>), shutdown_wait);
+}
+
+/// Same as [`start`], but runs the futures given by [`UserModule::call_async`] on `runtime`.
+///
+/// It is available with the `tokio` feature.
+#[cfg(feature = "tokio")]
+pub fn start_async<I: Ipc + 'static, T: UserModule + 'static>(args: Vec<String>, runtime: tokio::runtime::Handle) {
+    start_with_config::<I, T>(args, ModuleConfig {
+        runtime: Some(runtime),
+        ..ModuleConfig::default()
+    })
+}
+
+/// A handle to a module that has been started by [`start_with_handle`].
+pub struct ModuleHandle {
+    module: Arc<RwLock<dyn FoundryModule>>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl ModuleHandle {
+    /// Shuts down the module and waits for its thread to finish.
+    ///
+    /// This is equivalent to the coordinator calling [`FoundryModule::force_shutdown`],
+    /// and does nothing more than waiting if the coordinator has already shut it down.
+    pub fn stop(self) {
+        self.module.write().force_shutdown();
+        self.thread.join().unwrap();
+    }
+}
+
+/// Same as [`start_with_config`], but runs the module in a new thread and returns immediately.
+///
+/// The returned handle can stop the module without going through the coordinator.
+pub fn start_with_handle<I: Ipc + 'static, T: UserModule + 'static>(
+    args: Vec<String>,
+    config: ModuleConfig,
+) -> ModuleHandle {
+    let (module, shutdown_wait) = create_module_context::<T>(&config);
+    let module = Arc::new(RwLock::new(module)) as Arc<RwLock<dyn FoundryModule>>;
+    let module_ = Arc::clone(&module);
+    let thread = std::thread::spawn(move || serve::<I>(args, ServiceToExport::new(module_), shutdown_wait));
+    ModuleHandle {
+        module,
+        thread,
+    }
+}
+
+/// A handle to wait for a module that has been started by [`start_detached`] to be shut down.
+pub struct ShutdownWaiter {
+    /// Disconnected once the module is gone
+    finished: channel::Receiver<()>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl ShutdownWaiter {
+    /// Blocks until the module is shut down.
+    pub fn wait(self) {
+        self.thread.join().unwrap();
+    }
+
+    /// Tells whether the module has been shut down, without blocking.
+    pub fn try_wait(&self) -> bool {
+        self.finished.try_recv() == Err(channel::TryRecvError::Disconnected)
+    }
+}
+
+/// Same as [`start_with_config`], but runs the module in a new thread and returns immediately.
+///
+/// Unlike [`start_with_handle`], it leaves the shutdown to the coordinator,
+/// and the returned waiter only tells when it has happened.
+pub fn start_detached<I: Ipc + 'static, T: UserModule + 'static>(
+    args: Vec<String>,
+    config: ModuleConfig,
+) -> ShutdownWaiter {
+    let (module, shutdown_wait) = create_module_context::<T>(&config);
+    let (finished_signal, finished) = channel::bounded(0);
+    let thread = std::thread::spawn(move || {
+        serve::<I>(args, ServiceToExport::new(Box::new(module) as Box<dyn FoundryModule>), shutdown_wait);
+        drop(finished_signal);
+    });
+    ShutdownWaiter {
+        finished,
+        thread,
+    }
+}
+
+/// Creates a module waiting for the initialization, along with the receiver of its shutdown signal.
+fn create_module_context<T: UserModule + 'static>(
+    config: &ModuleConfig,
+) -> (ModuleContext<T>, channel::Receiver<channel::Sender<()>>) {
+    // The signal is buffered, so that the shutdown doesn't block on sending it, but waits for its acknowledgment.
+    let (shutdown_signal, shutdown_wait) = channel::bounded(1);
+    let module_name = module_name::<T>(config);
+    let module_id = panic_hook::next_module_id();
+    let mut module = ModuleContext::<T> {
         user_context: None,
-        exporting_service_pool: Arc::new(Mutex::new(ExportingServicePool::new())),
+        exporting_service_pool: Arc::new(RwLock::new(ExportingServicePool::new(config.lazy_exports))),
+        exports: Vec::new(),
         ports: HashMap::new(),
-        // TODO: decide thread pool size from the configuration
-        thread_pool: Arc::new(Mutex::new(ThreadPool::with_name("module_worker".to_owned(), 16))),
-        shutdown_signal,
+        thread_pool: Arc::new(Mutex::new(ThreadPool::with_name(
+            module_worker_name(&module_name, module_id),
+            config.worker_threads,
+        ))),
+        shared_thread_pool: false,
+        per_port_threads: config.per_port_threads,
+        retain_exports: config.retain_exports,
+        strict_exports: config.strict_exports,
+        rto_config_hook: config.rto_config_hook.clone(),
+        default_call_timeout: None,
+        #[cfg(feature = "tokio")]
+        runtime: config.runtime.clone(),
+        shutdown_signal: None,
         bootstrap_finished: false,
-    }) as Box<dyn FoundryModule>;
+        created_at: Instant::now(),
+        heartbeat: AtomicU64::new(0),
+        module_name,
+        module_id,
+        panic_hook: config.panic_hook.clone(),
+        panic_hook_id: None,
+        events: config.events.clone(),
+        span: span!("module", module = std::any::type_name::<T>()),
+    };
+    module.set_up(shutdown_signal);
+    (module, shutdown_wait)
+}
+
+/// Serves the module to the coordinator until it is shut down.
+fn serve<I: Ipc + 'static>(
+    args: Vec<String>,
+    module: ServiceToExport<dyn FoundryModule>,
+    shutdown_wait: channel::Receiver<channel::Sender<()>>,
+) {
+    let mut executee = fproc_sndbx::execution::executee::start::<I>(args);
 
     // rto configuration of the module itself (not each port) is not that important;
     // no need to take it from the coordinator
-    let config = RtoConfig::default_setup();
+    let rto_config = RtoConfig::default_setup();
     let (transport_send, transport_recv) = executee.ipc.take().unwrap().split();
-    let _ctx = remote_trait_object::Context::with_initial_service_export(
-        config,
-        transport_send,
-        transport_recv,
-        ServiceToExport::new(module),
-    );
-    shutdown_wait.recv().unwrap();
+    let _ctx =
+        remote_trait_object::Context::with_initial_service_export(rto_config, transport_send, transport_recv, module);
+    let taken = shutdown_wait.recv().unwrap();
+    // It is acknowledged before dropping the context, which may wait for the shutdown call to return.
+    let _ = taken.send(());
 }
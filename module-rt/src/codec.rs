@@ -0,0 +1,44 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// An encoding of the values that a module takes as bytes, chosen by [`TypedInit::Codec`].
+///
+/// The runtime itself doesn't care how the argument and the debug calls of a module are encoded,
+/// so it is only a contract between the module and its host, who must agree on the same codec.
+///
+/// [`TypedInit::Codec`]: ./trait.TypedInit.html#associatedtype.Codec
+pub trait ModuleCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// CBOR, which is what the rest of the crate uses.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cbor;
+
+impl ModuleCodec for Cbor {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(serde_cbor::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(serde_cbor::from_slice(data)?)
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Compression of the messages of a port, applied on top of its transport.
+
+use crate::coordinator_interface::Compression;
+use remote_trait_object::transport::{Terminate, TransportError, TransportRecv, TransportSend};
+use std::time::Duration;
+
+/// Wraps both ends of a transport to compress the messages going through them, if `compression` is given.
+///
+/// A message is never decompressed beyond `max_size`, whatever size the other end claims for it.
+pub fn wrap<S: TransportSend, R: TransportRecv>(
+    send: S,
+    recv: R,
+    compression: Option<Compression>,
+    max_size: usize,
+) -> (CompressingSend<S>, DecompressingRecv<R>) {
+    (
+        CompressingSend {
+            inner: send,
+            compression,
+            max_size,
+        },
+        DecompressingRecv {
+            inner: recv,
+            compression,
+            max_size,
+        },
+    )
+}
+
+pub struct CompressingSend<S> {
+    inner: S,
+    compression: Option<Compression>,
+    max_size: usize,
+}
+
+impl<S: TransportSend> TransportSend for CompressingSend<S> {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        match self.compression {
+            None => self.inner.send(data, timeout),
+            Some(Compression::Lz4) => {
+                // The other end would refuse to decompress it.
+                if data.len() > self.max_size {
+                    log::error!("Message of {} bytes is too large to be compressed", data.len());
+                    return Err(TransportError::Custom)
+                }
+                self.inner.send(&lz4_flex::compress_prepend_size(data), timeout)
+            }
+        }
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        self.inner.create_terminator()
+    }
+}
+
+pub struct DecompressingRecv<R> {
+    inner: R,
+    compression: Option<Compression>,
+    max_size: usize,
+}
+
+impl<R: TransportRecv> TransportRecv for DecompressingRecv<R> {
+    fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, TransportError> {
+        let data = self.inner.recv(timeout)?;
+        match self.compression {
+            None => Ok(data),
+            // The other end doesn't agree on the compression.
+            Some(Compression::Lz4) => decompress(&data, self.max_size),
+        }
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        self.inner.create_terminator()
+    }
+}
+
+/// Decompresses a message of LZ4 with its size prepended, checking the size before allocating anything for it.
+fn decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>, TransportError> {
+    let (size, compressed) = lz4_flex::block::uncompressed_size(data).map_err(|_| TransportError::Custom)?;
+    if size > max_size {
+        log::error!("Compressed message claims {} bytes, beyond {} bytes", size, max_size);
+        return Err(TransportError::Custom)
+    }
+    let mut decompressed = vec![0; size];
+    let len = lz4_flex::decompress_into(compressed, &mut decompressed).map_err(|_| TransportError::Custom)?;
+    // A message shorter than it claims has been corrupted.
+    if len != size {
+        return Err(TransportError::Custom)
+    }
+    Ok(decompressed)
+}
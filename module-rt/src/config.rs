@@ -0,0 +1,164 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crossbeam::channel::Sender;
+use remote_trait_object::Config as RtoConfig;
+use std::fmt;
+use std::panic::PanicInfo;
+use std::sync::Arc;
+
+/// Runtime configuration of a module.
+///
+/// Unlike the argument given to [`UserModule::new`], this is for the runtime itself, not for the user module.
+///
+/// [`UserModule::new`]: ./trait.UserModule.html#tymethod.new
+#[derive(Clone, Debug)]
+pub struct ModuleConfig {
+    /// Name of the module in the names of its threads and for the panic hook, the type name of the user module if not given
+    pub name: Option<String>,
+    /// Size of the thread pool that serves RTO calls for all the ports of the module
+    pub worker_threads: usize,
+    /// Whether to prepare each exported service on its first export, instead of at the initialization
+    ///
+    /// Services that no port exports are never constructed in this mode.
+    /// Failures to prepare services are reported on export, rather than by the initialization.
+    pub lazy_exports: bool,
+    /// Size of the thread pool that each port gets for itself, if given
+    ///
+    /// The ports share the pool of `worker_threads` by default, where a busy link can hold up the others.
+    /// With this, the shared pool only runs the jobs submitted by the user module.
+    pub per_port_threads: Option<usize>,
+    /// Whether to keep the services given at the initialization after the bootstrap,
+    /// so that the ports created by [`FoundryModule::create_dynamic_port`] export the same ones
+    ///
+    /// The services then live as long as the module, even if no link uses them anymore,
+    /// while they are dropped at the end of the bootstrap by default.
+    ///
+    /// [`FoundryModule::create_dynamic_port`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.create_dynamic_port
+    pub retain_exports: bool,
+    /// Hook that rewrites the RTO config of each link just before it is handed over to RTO, if given
+    ///
+    /// It sees the config assembled from the [`PartialRtoConfig`] given by the coordinator,
+    /// so it can change what the coordinator can't, like the names or the settings that RTO adds later.
+    ///
+    /// [`PartialRtoConfig`]: ./coordinator_interface/struct.PartialRtoConfig.html
+    pub rto_config_hook: Option<RtoConfigHook>,
+    /// Whether [`FoundryModule::finish_bootstrap`] fails if any of the services given at the initialization
+    /// has never been exported, instead of only warning about it
+    ///
+    /// Such a service is likely a mistake in the description of the links.
+    /// Nothing is checked with `retain_exports`, since the dynamic ports may still export them.
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.finish_bootstrap
+    pub strict_exports: bool,
+    /// Runtime that drives the futures given by [`UserModule::call_async`], only with the `tokio` feature
+    ///
+    /// [`UserModule::call_async`]: ./trait.UserModule.html#method.call_async
+    #[cfg(feature = "tokio")]
+    pub runtime: Option<tokio::runtime::Handle>,
+    /// Hook told the panics in the workers of the module until it is shut down, if given
+    ///
+    /// The workers are recognized by the names of their threads, which carry an id unique to the module
+    /// even among the modules of the same name, so the panics in the other threads,
+    /// including the ones that the user module spawns and a pool given to [`create_foundry_module_with_pool`],
+    /// go on to the hook that the process had before, as do the panics of the modules without a hook.
+    ///
+    /// [`create_foundry_module_with_pool`]: ./fn.create_foundry_module_with_pool.html
+    pub panic_hook: Option<PanicHook>,
+    /// Channel that the module tells the changes of its state to, if given
+    ///
+    /// A module whose channel has been closed keeps running without telling anything.
+    pub events: Option<Sender<ModuleEvent>>,
+}
+
+impl Default for ModuleConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            worker_threads: 16,
+            lazy_exports: false,
+            per_port_threads: None,
+            retain_exports: false,
+            rto_config_hook: None,
+            strict_exports: false,
+            #[cfg(feature = "tokio")]
+            runtime: None,
+            panic_hook: None,
+            events: None,
+        }
+    }
+}
+
+/// A change of the state of a module, sent to [`ModuleConfig::events`].
+///
+/// [`ModuleConfig::events`]: ./struct.ModuleConfig.html#structfield.events
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModuleEvent {
+    /// The user module has been created, either by the initialization or on the creation of the module.
+    Initialized,
+    PortCreated {
+        name: String,
+    },
+    BootstrapFinished,
+    ShutDown,
+}
+
+/// A panic hook given by [`ModuleConfig::panic_hook`], which is told the name of the module along with the panic.
+///
+/// The name is [`ModuleConfig::name`], or the type name of the user module if it is not given.
+///
+/// [`ModuleConfig::name`]: ./struct.ModuleConfig.html#structfield.name
+/// [`ModuleConfig::panic_hook`]: ./struct.ModuleConfig.html#structfield.panic_hook
+#[derive(Clone)]
+pub struct PanicHook(Arc<dyn Fn(&PanicInfo<'_>, &str) + Send + Sync>);
+
+impl PanicHook {
+    pub fn new(hook: impl Fn(&PanicInfo<'_>, &str) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    pub(crate) fn call(&self, info: &PanicInfo<'_>, module_name: &str) {
+        (self.0)(info, module_name)
+    }
+}
+
+impl fmt::Debug for PanicHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PanicHook")
+    }
+}
+
+/// A hook given by [`ModuleConfig::rto_config_hook`], which returns the RTO config to link a port with.
+///
+/// [`ModuleConfig::rto_config_hook`]: ./struct.ModuleConfig.html#structfield.rto_config_hook
+#[derive(Clone)]
+pub struct RtoConfigHook(Arc<dyn Fn(RtoConfig) -> RtoConfig + Send + Sync>);
+
+impl RtoConfigHook {
+    pub fn new(hook: impl Fn(RtoConfig) -> RtoConfig + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    pub(crate) fn call(&self, rto_config: RtoConfig) -> RtoConfig {
+        (self.0)(rto_config)
+    }
+}
+
+impl fmt::Debug for RtoConfigHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RtoConfigHook")
+    }
+}
@@ -25,17 +25,58 @@
 //! [`FoundryModule`]: ./trait.FoundryModule.html
 //! [`Port`]: ./trait.Port.html
 
+use crate::error::{ConfigError, ImportError, ModuleError, PoolError};
+#[cfg(unix)]
+use fproc_sndbx::ipc::{unix_socket::DomainSocket, Ipc};
+use parking_lot::Mutex;
 use raw_exchange::HandleToExchange;
 use remote_trait_object::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use threadpool::ThreadPool;
 
-/// Same as `remote_trait_object::Config` except the thread pool.
-#[derive(Debug, Serialize, Deserialize)]
+/// Same as `remote_trait_object::Config` except the thread pool, along with the settings of the transport.
+///
+/// Every field is carried to the `Config` of the port, so the coordinator decides all of them for each link.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PartialRtoConfig {
     pub name: String,
     pub call_slots: usize,
     pub call_timeout: Option<std::time::Duration>,
     pub maximum_services_num: usize,
+    /// Compression of the messages over the link, which both ends must agree on
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// Size in bytes of the largest message that the port sends, before the compression
+    ///
+    /// A larger one fails to be sent, as the link is broken, instead of being handed to the transport,
+    /// and the module reports it with [`ModuleError::MessageTooLarge`].
+    /// It is checked by the sending end, and by the receiving end only for a compressed message,
+    /// which is never decompressed beyond it, or beyond 64 MiB, the largest frame of `Tcp`, without it.
+    /// So both ends should be given the same.
+    ///
+    /// [`ModuleError::MessageTooLarge`]: ../enum.ModuleError.html#variant.MessageTooLarge
+    #[serde(default)]
+    pub max_message_size: Option<usize>,
+    /// How long [`Port::initialize`] waits for the transport to be established, including the handshake
+    ///
+    /// It waits as long as the transport does without it, which is forever for `Intra` and `DomainSocket`.
+    ///
+    /// [`Port::initialize`]: ./trait.Port.html#tymethod.initialize
+    #[serde(default)]
+    pub init_timeout: Option<Duration>,
+}
+
+/// Compression algorithm of [`PartialRtoConfig::compression`].
+///
+/// [`PartialRtoConfig::compression`]: ./struct.PartialRtoConfig.html#structfield.compression
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    Lz4,
 }
 
 impl PartialRtoConfig {
@@ -45,18 +86,499 @@ impl PartialRtoConfig {
             call_slots: config.call_slots,
             call_timeout: config.call_timeout,
             maximum_services_num: config.maximum_services_num,
+            compression: None,
+            max_message_size: None,
+            init_timeout: None,
         }
     }
+
+    /// Rebuilds the full configuration with the thread pool, which is what the port of the link runs with.
+    ///
+    /// `compression`, `max_message_size` and `init_timeout` are not a part of it, as they apply to the transport.
+    pub fn into_rto_config(self, thread_pool: Arc<Mutex<ThreadPool>>) -> Config {
+        Config {
+            name: self.name,
+            call_slots: self.call_slots,
+            call_timeout: self.call_timeout,
+            maximum_services_num: self.maximum_services_num,
+            thread_pool,
+        }
+    }
+
+    /// Starts building a configuration from the values of `Config::default_setup()`.
+    pub fn builder() -> PartialRtoConfigBuilder {
+        PartialRtoConfigBuilder {
+            config: Self::from_rto_config(Config::default_setup()),
+        }
+    }
+}
+
+/// A builder of [`PartialRtoConfig`], created by [`PartialRtoConfig::builder`].
+///
+/// [`PartialRtoConfig`]: ./struct.PartialRtoConfig.html
+/// [`PartialRtoConfig::builder`]: ./struct.PartialRtoConfig.html#method.builder
+#[derive(Debug)]
+pub struct PartialRtoConfigBuilder {
+    config: PartialRtoConfig,
+}
+
+impl PartialRtoConfigBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.config.name = name.into();
+        self
+    }
+
+    pub fn call_slots(mut self, call_slots: usize) -> Self {
+        self.config.call_slots = call_slots;
+        self
+    }
+
+    pub fn call_timeout(mut self, call_timeout: Option<Duration>) -> Self {
+        self.config.call_timeout = call_timeout;
+        self
+    }
+
+    pub fn maximum_services_num(mut self, maximum_services_num: usize) -> Self {
+        self.config.maximum_services_num = maximum_services_num;
+        self
+    }
+
+    pub fn compression(mut self, compression: Option<Compression>) -> Self {
+        self.config.compression = compression;
+        self
+    }
+
+    pub fn max_message_size(mut self, max_message_size: Option<usize>) -> Self {
+        self.config.max_message_size = max_message_size;
+        self
+    }
+
+    pub fn init_timeout(mut self, init_timeout: Option<Duration>) -> Self {
+        self.config.init_timeout = init_timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<PartialRtoConfig, ConfigError> {
+        if self.config.call_slots == 0 {
+            return Err(ConfigError::ZeroCallSlots)
+        }
+        Ok(self.config)
+    }
+}
+
+/// Version of the protocol between the ports, which both ends of a link must agree on.
+///
+/// It is exchanged by [`Port::initialize`] before anything else goes through the link.
+///
+/// [`Port::initialize`]: ./trait.Port.html#tymethod.initialize
+pub const PORT_PROTOCOL_VERSION: u32 = 1;
+
+/// Transport that a port uses to communicate with the other end of the link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransportKind {
+    /// Channel between threads in the same process
+    Intra,
+    /// Unix domain socket between processes in the same host, only on Unix
+    DomainSocket,
+    /// TCP connection, possibly between different hosts
+    Tcp,
+    /// Named pipes between processes in the same host, only on Windows
+    NamedPipe,
+    /// Queue between threads in the same process that a test steps through, only with the `testing` feature
+    ///
+    /// Its `ipc_arg` is given by [`DeterministicLink::arguments_for_both_ends`].
+    ///
+    /// [`DeterministicLink::arguments_for_both_ends`]: ../testing/struct.DeterministicLink.html#method.arguments_for_both_ends
+    Deterministic,
+    /// Unix domain socket between processes in the same host, along with a shared memory segment for large messages,
+    /// only on Unix
+    SharedMemory,
+}
+
+/// A snapshot of the traffic of a port, returned by [`Port::stats`].
+///
+/// Messages are counted on the transport, so they include the calls issued through the port,
+/// the replies to the calls served by the port, and whatever else RTO sends, like dropping remote services.
+///
+/// [`Port::stats`]: ./trait.Port.html#tymethod.stats
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortStats {
+    /// Number of services exported through the port
+    pub exported: usize,
+    /// Number of services imported through the port
+    pub imported: usize,
+    pub sent_messages: usize,
+    pub received_messages: usize,
+    pub sent_bytes: usize,
+    pub received_bytes: usize,
+}
+
+/// A snapshot of the whole module, returned by [`FoundryModule::metrics`].
+///
+/// [`FoundryModule::metrics`]: ./trait.FoundryModule.html#tymethod.metrics
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleMetrics {
+    /// Time since the module has been created
+    pub uptime: Duration,
+    /// Stats of each port by its name, leaving out the disconnected ones
+    pub ports: BTreeMap<String, PortStats>,
+    /// Number of services exported through all the ports
+    pub exported_count: usize,
+    /// Number of services imported through all the ports
+    pub imported_count: usize,
+}
+
+/// Settings of the runtime of a module, given along with the initialization by [`FoundryModule::initialize_with_runtime_config`].
+///
+/// Each of them leaves the corresponding setting of [`ModuleConfig`] as it is if not given.
+///
+/// [`FoundryModule::initialize_with_runtime_config`]: ./trait.FoundryModule.html#tymethod.initialize_with_runtime_config
+/// [`ModuleConfig`]: ../struct.ModuleConfig.html
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleRuntimeConfig {
+    /// Number of the workers of the thread pool of the module, instead of [`ModuleConfig::worker_threads`]
+    ///
+    /// It is rejected for a module whose pool is shared with other modules.
+    ///
+    /// [`ModuleConfig::worker_threads`]: ../struct.ModuleConfig.html#structfield.worker_threads
+    pub worker_threads: Option<usize>,
+    /// Call timeout of the ports whose [`PartialRtoConfig`] doesn't have one
+    ///
+    /// [`PartialRtoConfig`]: ./struct.PartialRtoConfig.html
+    pub call_timeout: Option<Duration>,
+}
+
+/// A snapshot of the thread pool of the module, returned by [`FoundryModule::pool_stats`].
+///
+/// [`FoundryModule::pool_stats`]: ./trait.FoundryModule.html#tymethod.pool_stats
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Number of the workers, given by [`ModuleConfig::worker_threads`] unless the pool is shared
+    ///
+    /// [`ModuleConfig::worker_threads`]: ../struct.ModuleConfig.html#structfield.worker_threads
+    pub size: usize,
+    /// Number of the workers running a job
+    pub active: usize,
+    /// Number of the jobs waiting for a worker
+    pub queued: usize,
+}
+
+/// A port of a module, returned by [`FoundryModule::list_ports`].
+///
+/// [`FoundryModule::list_ports`]: ./trait.FoundryModule.html#tymethod.list_ports
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortInfo {
+    pub name: String,
+    /// Name of the module that the port is linked with, given to [`FoundryModule::create_port`]
+    ///
+    /// [`FoundryModule::create_port`]: ./trait.FoundryModule.html#tymethod.create_port
+    pub connected_module: String,
+    pub initialized: bool,
+    /// Name that RTO knows the link by, once the port is initialized
+    ///
+    /// Unless the coordinator has named it, it is derived from the default name and `connected_module`.
+    pub rto_name: Option<String>,
+}
+
+/// State of a link, returned by [`FoundryModule::check_links`].
+///
+/// [`FoundryModule::check_links`]: ./trait.FoundryModule.html#tymethod.check_links
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkHealth {
+    /// Name of the module that the services have been imported from
+    pub module: String,
+    pub status: LinkStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkStatus {
+    /// The imported service has answered.
+    Alive,
+    /// The call to the imported service has failed, usually by a timeout.
+    Dead {
+        message: String,
+    },
+}
+
+/// Where a module is in its lifecycle, reported by [`FoundryModule::state`].
+///
+/// [`FoundryModule::state`]: ./trait.FoundryModule.html#tymethod.state
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModuleState {
+    /// The user module has not been created yet, or has failed to be created.
+    Uninitialized,
+    /// The ports are being created and linked.
+    Initialized,
+    /// The bootstrap has been finished, so the module is running.
+    Bootstrapped,
+    /// The module has been shut down.
+    ShutDown,
+}
+
+/// How [`FoundryModule::shutdown_with_timeout`] has ended.
+///
+/// [`FoundryModule::shutdown_with_timeout`]: ./trait.FoundryModule.html#tymethod.shutdown_with_timeout
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShutdownOutcome {
+    /// Every port has been torn down in time.
+    Clean,
+    /// Some ports haven't been torn down in time, and have been left behind.
+    Forced {
+        /// Names of the ports that haven't been torn down
+        undrained_ports: Vec<String>,
+    },
+    /// The user module has rejected to be shut down, so nothing has been torn down.
+    Rejected,
+}
+
+/// `ipc_arg` of a port linked over [`TransportKind::Tcp`], encoded in CBOR.
+///
+/// [`TransportKind::Tcp`]: ./enum.TransportKind.html#variant.Tcp
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TcpEnd {
+    /// Waits for the other end to connect on the address
+    Listen(SocketAddr),
+    /// Connects to the other end listening on the address
+    Connect(SocketAddr),
+}
+
+impl TcpEnd {
+    /// Creates `ipc_arg`s for both ends of a link, where the first one listens on `addr`.
+    pub fn arguments_for_both_ends(addr: SocketAddr) -> (Vec<u8>, Vec<u8>) {
+        (serde_cbor::to_vec(&TcpEnd::Listen(addr)).unwrap(), serde_cbor::to_vec(&TcpEnd::Connect(addr)).unwrap())
+    }
+}
+
+/// `ipc_arg` of a port linked over [`TransportKind::NamedPipe`], encoded in CBOR.
+///
+/// [`TransportKind::NamedPipe`]: ./enum.TransportKind.html#variant.NamedPipe
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NamedPipeEnd {
+    /// Creates the pipes with the name and waits for the other end to connect
+    Listen(String),
+    /// Connects to the pipes that the other end has created with the name
+    Connect(String),
+}
+
+impl NamedPipeEnd {
+    /// Creates `ipc_arg`s for both ends of a link, where the first one creates the pipes.
+    pub fn arguments_for_both_ends(name: &str) -> (Vec<u8>, Vec<u8>) {
+        (
+            serde_cbor::to_vec(&NamedPipeEnd::Listen(name.to_owned())).unwrap(),
+            serde_cbor::to_vec(&NamedPipeEnd::Connect(name.to_owned())).unwrap(),
+        )
+    }
+}
+
+/// `ipc_arg` of a port linked over [`TransportKind::SharedMemory`], encoded in CBOR.
+///
+/// The segment is a file, which is best placed in a memory-backed file system like `/dev/shm`.
+/// It is removed as soon as both ends have mapped it.
+///
+/// [`TransportKind::SharedMemory`]: ./enum.TransportKind.html#variant.SharedMemory
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SharedMemoryEnd {
+    /// Creates the segment at the path and waits for the other end on the socket
+    Create {
+        /// `ipc_arg` of the domain socket
+        socket: Vec<u8>,
+        segment: PathBuf,
+        /// Size of the largest message that goes through the segment, in each direction
+        capacity: usize,
+    },
+    /// Maps the segment that the other end has created at the path
+    Open {
+        /// `ipc_arg` of the domain socket
+        socket: Vec<u8>,
+        segment: PathBuf,
+        /// Size of the largest message that goes through the segment, in each direction
+        capacity: usize,
+    },
+}
+
+#[cfg(unix)]
+impl SharedMemoryEnd {
+    /// Creates `ipc_arg`s for both ends of a link, where the first one creates the segment at `segment`.
+    pub fn arguments_for_both_ends(segment: PathBuf, capacity: usize) -> (Vec<u8>, Vec<u8>) {
+        let (socket1, socket2) = DomainSocket::arguments_for_both_ends();
+        (
+            serde_cbor::to_vec(&SharedMemoryEnd::Create {
+                socket: socket1,
+                segment: segment.clone(),
+                capacity,
+            })
+            .unwrap(),
+            serde_cbor::to_vec(&SharedMemoryEnd::Open {
+                socket: socket2,
+                segment,
+                capacity,
+            })
+            .unwrap(),
+        )
+    }
 }
 
 /// A service trait that represents a module that the Foundry host will communicate through.
 #[service]
 pub trait FoundryModule: Service {
-    fn initialize(&mut self, arg: &[u8], exports: &[(String, Vec<u8>)]);
-    fn create_port(&mut self, name: &str) -> ServiceRef<dyn Port>;
-    fn finish_bootstrap(&mut self);
-    fn debug(&mut self, arg: &[u8]) -> Vec<u8>;
-    fn shutdown(&mut self);
+    /// Same as `initialize_with_keys`, but keys the exports by their indices, such as `"0"`.
+    ///
+    /// Deprecated in favor of `initialize_with_keys`, and kept for the coordinators that pair the services by position.
+    fn initialize(&mut self, arg: &[u8], exports: &[(String, Vec<u8>)]) -> Result<(), ModuleError>;
+    /// Creates the user module with `arg` and loads the exports, each of which is given as `(key, ctor_name, ctor_arg)`.
+    ///
+    /// The keys must be unique, so that [`Port::export_keys`] can pick the services regardless of their order.
+    /// An export keeps its index in the order given here, for [`Port::export`].
+    ///
+    /// [`Port::export_keys`]: ./trait.Port.html#tymethod.export_keys
+    /// [`Port::export`]: ./trait.Port.html#tymethod.export
+    fn initialize_with_keys(&mut self, arg: &[u8], exports: &[(String, String, Vec<u8>)]) -> Result<(), ModuleError>;
+    /// Same as `initialize_with_keys`, but also applies `runtime_config` to the runtime of the module.
+    ///
+    /// `arg` is only for the user module, while `runtime_config` is read by the runtime itself.
+    /// It is applied once the user module has been created, so a module that fails to be initialized keeps its runtime.
+    fn initialize_with_runtime_config(
+        &mut self,
+        arg: &[u8],
+        exports: &[(String, String, Vec<u8>)],
+        runtime_config: ModuleRuntimeConfig,
+    ) -> Result<(), ModuleError>;
+    /// Creates a port for the link with the module of `name`.
+    ///
+    /// It fails with [`ModuleError::DuplicatePort`] if the module already has a port of the name,
+    /// or with [`ModuleError::BootstrapAlreadyFinished`] after `finish_bootstrap`,
+    /// when `create_dynamic_port` adds a link instead.
+    ///
+    /// [`ModuleError::DuplicatePort`]: ../enum.ModuleError.html#variant.DuplicatePort
+    /// [`ModuleError::BootstrapAlreadyFinished`]: ../enum.ModuleError.html#variant.BootstrapAlreadyFinished
+    fn create_port(&mut self, name: &str) -> Result<ServiceRef<dyn Port>, ModuleError>;
+    /// Same as `create_port`, but creates a port for each of `names` in a single call.
+    ///
+    /// Each name is checked on its own, so a duplicate fails only its own entry.
+    fn create_ports(&mut self, names: &[String]) -> Vec<Result<ServiceRef<dyn Port>, ModuleError>>;
+    /// Same as `create_port`, but for a link that is added after `finish_bootstrap`.
+    ///
+    /// The port exports from its own pool of the services given at the initialization,
+    /// each of which is prepared on its first export.
+    /// With [`ModuleConfig::retain_exports`], it exports the same services as the ports of the bootstrap instead.
+    /// It fails with [`ModuleError::BootstrapNotFinished`] before `finish_bootstrap`.
+    ///
+    /// [`ModuleConfig::retain_exports`]: ../struct.ModuleConfig.html#structfield.retain_exports
+    /// [`ModuleError::BootstrapNotFinished`]: ../enum.ModuleError.html#variant.BootstrapNotFinished
+    fn create_dynamic_port(&mut self, name: &str) -> Result<ServiceRef<dyn Port>, ModuleError>;
+    /// Ends the bootstrapping, clearing the services that have been prepared for the exports.
+    ///
+    /// It fails with [`ModuleError::PortNotInitialized`] if any port has been created but not initialized,
+    /// or with [`ModuleError::UnexportedServices`] if any service would be cleared without ever being exported
+    /// under [`ModuleConfig::strict_exports`], in which case nothing is changed.
+    /// A second call fails with [`ModuleError::BootstrapAlreadyFinished`],
+    /// and a call before the initialization or after the shutdown with [`ModuleError::NotInitialized`]
+    /// or [`ModuleError::ShutDown`].
+    ///
+    /// [`ModuleError::PortNotInitialized`]: ../enum.ModuleError.html#variant.PortNotInitialized
+    /// [`ModuleError::NotInitialized`]: ../enum.ModuleError.html#variant.NotInitialized
+    /// [`ModuleError::ShutDown`]: ../enum.ModuleError.html#variant.ShutDown
+    /// [`ModuleError::BootstrapAlreadyFinished`]: ../enum.ModuleError.html#variant.BootstrapAlreadyFinished
+    /// [`ModuleError::UnexportedServices`]: ../enum.ModuleError.html#variant.UnexportedServices
+    /// [`ModuleConfig::strict_exports`]: ../struct.ModuleConfig.html#structfield.strict_exports
+    fn finish_bootstrap(&mut self) -> Result<(), ModuleError>;
+    /// Calls [`UserModule::debug`], reporting [`ModuleError::Panicked`] if it panics.
+    ///
+    /// It doesn't block the other calls that take `&self`, like `heartbeat`.
+    /// Without the user module, before the initialization or after the shutdown,
+    /// it fails with [`ModuleError::NotInitialized`] or [`ModuleError::ShutDown`], as `call` and `check_links` do.
+    ///
+    /// [`UserModule::debug`]: ../trait.UserModule.html#tymethod.debug
+    /// [`ModuleError::Panicked`]: ../enum.ModuleError.html#variant.Panicked
+    /// [`ModuleError::NotInitialized`]: ../enum.ModuleError.html#variant.NotInitialized
+    /// [`ModuleError::ShutDown`]: ../enum.ModuleError.html#variant.ShutDown
+    fn debug(&self, arg: &[u8]) -> Result<Vec<u8>, ModuleError>;
+    /// Calls [`UserModule::call`], reporting [`ModuleError::Panicked`] if it panics.
+    ///
+    /// [`UserModule::call`]: ../trait.UserModule.html#method.call
+    /// [`ModuleError::Panicked`]: ../enum.ModuleError.html#variant.Panicked
+    fn call(&self, method: &str, arg: &[u8]) -> Result<Vec<u8>, ModuleError>;
+    /// Pings the linked modules through the services imported from them, with [`UserModule::ping_imports`].
+    ///
+    /// Unlike `heartbeat`, which tells that this module is alive, this tells whether the modules that it depends on are.
+    ///
+    /// [`UserModule::ping_imports`]: ../trait.UserModule.html#method.ping_imports
+    fn check_links(&self) -> Result<Vec<LinkHealth>, ModuleError>;
+    /// Returns a counter that increases on every heartbeat, to tell that the module is still responding.
+    ///
+    /// It never waits for the user module, so it answers even while `debug` or `call` is running.
+    fn heartbeat(&self) -> u64;
+    /// Collects the stats of all the ports at once.
+    fn metrics(&self) -> ModuleMetrics;
+    /// Returns the number of the calls to the exported services that are being served or waiting for a worker,
+    /// including the jobs submitted by the user module to its thread pool.
+    ///
+    /// They can't be cancelled, since neither RTO nor the thread pool can interrupt a job,
+    /// but `shutdown_with_timeout` can leave them behind.
+    fn inflight_calls(&self) -> usize;
+    /// Tells how busy the thread pool of the module is.
+    ///
+    /// Unlike `inflight_calls`, it leaves out the pools of the ports given by [`ModuleConfig::per_port_threads`].
+    /// A pool shared with other modules is counted with their jobs too.
+    ///
+    /// [`ModuleConfig::per_port_threads`]: ../struct.ModuleConfig.html#structfield.per_port_threads
+    fn pool_stats(&self) -> PoolStats;
+    /// Returns the number of the services loaded from the `exports` given to the initialization.
+    ///
+    /// It includes the ones that have failed to be prepared,
+    /// and drops to zero once the bootstrap is finished unless [`ModuleConfig::retain_exports`] is set.
+    ///
+    /// [`ModuleConfig::retain_exports`]: ../struct.ModuleConfig.html#structfield.retain_exports
+    fn exported_count(&self) -> usize;
+    /// Returns the number of the imported services that the user module reports by [`UserModule::imported_count`].
+    ///
+    /// [`UserModule::imported_count`]: ../trait.UserModule.html#method.imported_count
+    fn imported_count(&self) -> usize;
+    /// Returns the names of the imported services that the user module reports by [`UserModule::imported_names`].
+    ///
+    /// [`UserModule::imported_names`]: ../trait.UserModule.html#method.imported_names
+    fn imported_names(&self) -> Vec<String>;
+    /// Returns the number of the proxies that the user module reports by [`UserModule::retained_proxy_count`].
+    ///
+    /// [`UserModule::retained_proxy_count`]: ../trait.UserModule.html#method.retained_proxy_count
+    fn retained_proxy_count(&self) -> usize;
+    /// Tells where the module is in the lifecycle described by `reinitialize`.
+    fn state(&self) -> ModuleState;
+    /// Lists the ports of the module in the order of their names, leaving out the disconnected ones.
+    fn list_ports(&self) -> Vec<PortInfo>;
+    /// Tears down the module, unless [`UserModule::can_shutdown`] rejects it with [`ModuleError::ShutdownRejected`].
+    ///
+    /// A rejected shutdown leaves the module as it was, so the coordinator can retry it later,
+    /// or escalate to `force_shutdown` if the module keeps rejecting it.
+    /// A repeated shutdown does nothing.
+    /// It fails with [`ModuleError::ShutdownSignalTimeout`] if the thread serving the module doesn't answer in time,
+    /// but the module has been torn down anyway.
+    ///
+    /// [`UserModule::can_shutdown`]: ../trait.UserModule.html#method.can_shutdown
+    /// [`ModuleError::ShutdownRejected`]: ../enum.ModuleError.html#variant.ShutdownRejected
+    /// [`ModuleError::ShutdownSignalTimeout`]: ../enum.ModuleError.html#variant.ShutdownSignalTimeout
+    fn shutdown(&mut self) -> Result<(), ModuleError>;
+    /// Same as `shutdown`, but never asks the user module whether it can be shut down.
+    fn force_shutdown(&mut self);
+    /// Same as `shutdown`, but gives up waiting for the ports that are not torn down within `timeout`.
+    ///
+    /// The ports are torn down in parallel, and the ones left behind are dropped without waiting for them.
+    /// A repeated shutdown does nothing and is considered to be clean.
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> ShutdownOutcome;
+    /// Initializes the module again after it has been shut down, with a new user module and exports.
+    ///
+    /// The module goes through the following states, and this is the only way back from the last one.
+    /// - Created: `initialize` moves it to Initialized.
+    /// - Initialized: ports are created and linked, and `finish_bootstrap` moves it to Running.
+    /// - Running: dynamic ports can be added, and `shutdown` moves it to Shut down from here or Initialized.
+    /// - Shut down: `reinitialize` moves it to Initialized, with no ports and the bootstrap to be finished again.
+    ///
+    /// It fails with [`ModuleError::NotShutDown`] in any other state.
+    /// A module run by [`start`] stops serving once it is shut down, so this is for the ones that are not.
+    ///
+    /// [`ModuleError::NotShutDown`]: ../enum.ModuleError.html#variant.NotShutDown
+    /// [`start`]: ../fn.start.html
+    fn reinitialize(&mut self, arg: &[u8], exports: &[(String, Vec<u8>)]) -> Result<(), ModuleError>;
 }
 
 /// A service trait that represents a port to be bootstrapped.
@@ -67,9 +589,83 @@ pub trait FoundryModule: Service {
 /// In most case where you want to export/import service objects, you will just use [`SBox`], [`SArc`], or [`SRwLock`].
 /// However since it is for the bootstrapping where the exact types are erased and it is expected
 /// for the importer to cast it as he wants, we have this special interface.
+///
+/// Each of the exports and `import` is a single RTO call that carries the whole slice,
+/// so exchanging many services doesn't take more round-trips.
+/// Exchanging handles doesn't make any traffic on the link of the port itself.
 #[service]
 pub trait Port: Service {
-    fn initialize(&mut self, rto_config: PartialRtoConfig, ipc_arg: Vec<u8>, intra: bool);
-    fn export(&mut self, ids: &[usize]) -> Vec<HandleToExchange>;
-    fn import(&mut self, slots: &[(String, HandleToExchange)]);
+    fn initialize(
+        &mut self,
+        rto_config: PartialRtoConfig,
+        ipc_arg: Vec<u8>,
+        transport: TransportKind,
+    ) -> Result<(), ModuleError>;
+    /// Links the port again over a new transport, after the old one has been severed, like by a restart of the other end.
+    ///
+    /// The old link is torn down as `disconnect` does, but the port stays in the module,
+    /// and the new link is made with the same configuration and transport as `initialize`, including the handshake.
+    /// Nothing that has been exchanged over the old link works anymore, so the services must be exchanged again;
+    /// after [`FoundryModule::finish_bootstrap`], only by `export_skeleton` or with [`ModuleConfig::retain_exports`].
+    /// It fails with [`ModuleError::PortNotInitialized`] if the port has never been initialized.
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./trait.FoundryModule.html#tymethod.finish_bootstrap
+    /// [`ModuleConfig::retain_exports`]: ../struct.ModuleConfig.html#structfield.retain_exports
+    /// [`ModuleError::PortNotInitialized`]: ../enum.ModuleError.html#variant.PortNotInitialized
+    fn reconnect(&mut self, ipc_arg: Vec<u8>) -> Result<(), ModuleError>;
+    /// Tells whether the port has been linked by `initialize` or `reconnect`, and not disconnected since.
+    ///
+    /// [`FoundryModule::finish_bootstrap`] fails on a port that isn't, and the shutdown leaves it out.
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./trait.FoundryModule.html#tymethod.finish_bootstrap
+    fn is_initialized(&self) -> bool;
+    /// Exports the services prepared at the initialization, by their indices in the `exports`.
+    ///
+    /// A service can be exported through several ports of the module until [`FoundryModule::finish_bootstrap`],
+    /// in which case all of them share the same service object.
+    /// Prefer `export_keys`, which doesn't depend on the order of the `exports`.
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./trait.FoundryModule.html#tymethod.finish_bootstrap
+    fn export(&mut self, ids: &[usize]) -> Result<Vec<HandleToExchange>, PoolError>;
+    /// Same as `export`, but looks up the services by the keys given to [`FoundryModule::initialize_with_keys`].
+    ///
+    /// [`FoundryModule::initialize_with_keys`]: ./trait.FoundryModule.html#tymethod.initialize_with_keys
+    fn export_keys(&mut self, keys: &[String]) -> Result<Vec<HandleToExchange>, PoolError>;
+    /// Same as `export`, but looks up the services by their constructor names, which must be unique.
+    fn export_named(&mut self, names: &[String]) -> Result<Vec<HandleToExchange>, PoolError>;
+    /// Same as `export`, but exports all the services of the `exports`, returning the handles in the same order.
+    ///
+    /// It fails if any of them has failed to be prepared, like `export` does.
+    fn export_all(&mut self) -> Result<Vec<HandleToExchange>, PoolError>;
+    /// Exports the services that the module has made at runtime, taken by [`UserModule::take_dynamic_export`] for each key.
+    ///
+    /// It doesn't depend on the `exports` of the initialization, so it works even after [`FoundryModule::finish_bootstrap`].
+    ///
+    /// [`UserModule::take_dynamic_export`]: ../trait.UserModule.html#method.take_dynamic_export
+    /// [`FoundryModule::finish_bootstrap`]: ./trait.FoundryModule.html#tymethod.finish_bootstrap
+    fn export_skeleton(&mut self, keys: &[String]) -> Result<Vec<HandleToExchange>, PoolError>;
+    /// Imports the services, returning the names of the ones that have been imported.
+    ///
+    /// Every slot is checked by [`UserModule::validate_import`] first, and nothing is imported if any of them is rejected.
+    /// Once they are imported, a failure of a slot doesn't stop the others from being tried,
+    /// but the ones that have been imported are given to [`UserModule::revoke_import`] afterwards,
+    /// so that either all the slots are imported or none of them is.
+    /// All the failures are reported together by [`ImportError::Failed`].
+    /// A handle may appear under several names, in which case it is imported once under the first name
+    /// and the rest are given to [`UserModule::alias_import`].
+    ///
+    /// [`UserModule::validate_import`]: ../trait.UserModule.html#method.validate_import
+    /// [`UserModule::revoke_import`]: ../trait.UserModule.html#method.revoke_import
+    /// [`UserModule::alias_import`]: ../trait.UserModule.html#method.alias_import
+    /// [`ImportError::Failed`]: ../enum.ImportError.html#variant.Failed
+    fn import(&mut self, slots: &[(String, HandleToExchange)]) -> Result<Vec<String>, ImportError>;
+    /// Same as `import`, but takes the slots by their names, which are imported in the order of the names.
+    fn import_map(&mut self, map: HashMap<String, HandleToExchange>) -> Result<Vec<String>, ImportError>;
+    fn stats(&self) -> PortStats;
+    /// Tears down the link, dropping the services exchanged through it.
+    ///
+    /// The other ports of the module are not affected.
+    /// The port is removed from the module, so its name can be used for a new port.
+    /// The port itself can't be linked again, and fails to export or import with a `Disconnected` error.
+    fn disconnect(&mut self);
 }
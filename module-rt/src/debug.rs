@@ -0,0 +1,57 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Helpers to call [`FoundryModule::debug`] with typed values, encoded in CBOR like the rest of the crate or by another codec.
+//!
+//! [`FoundryModule::debug`]: ../coordinator_interface/trait.FoundryModule.html#tymethod.debug
+
+use crate::codec::{Cbor, ModuleCodec};
+use crate::coordinator_interface::FoundryModule;
+use crate::error::DebugError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encodes `request`, calls [`FoundryModule::debug`] with it, and decodes the response.
+///
+/// The module is expected to decode the argument and encode its result with `serde_cbor`.
+///
+/// [`FoundryModule::debug`]: ../coordinator_interface/trait.FoundryModule.html#tymethod.debug
+pub fn call_typed<Req: Serialize, Resp: DeserializeOwned>(
+    module: &dyn FoundryModule,
+    request: &Req,
+) -> Result<Resp, DebugError> {
+    call_with_codec::<Cbor, _, _>(module, request)
+}
+
+/// Same as [`call_typed`], but encodes the request and decodes the response by `C`.
+///
+/// It is what a module implementing [`TypedDebug`] expects, with the codec of the module.
+///
+/// [`call_typed`]: ./fn.call_typed.html
+/// [`TypedDebug`]: ../trait.TypedDebug.html
+pub fn call_with_codec<C: ModuleCodec, Req: Serialize, Resp: DeserializeOwned>(
+    module: &dyn FoundryModule,
+    request: &Req,
+) -> Result<Resp, DebugError> {
+    let codec_failed = |err: Box<dyn std::error::Error + Send + Sync>| DebugError::Codec {
+        message: err.to_string(),
+    };
+    let arg = C::encode(request).map_err(codec_failed)?;
+    let response = module.debug(&arg).map_err(|error| DebugError::Module {
+        error,
+    })?;
+    C::decode(&response).map_err(codec_failed)
+}
@@ -0,0 +1,226 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A transport between threads in the same process, which holds every message until the test delivers it.
+//!
+//! Ports are linked over it with [`TransportKind::Deterministic`], so that a test can decide
+//! the order in which the messages of both ends arrive, instead of leaving it to the threads.
+//! It is available with the `testing` feature.
+//!
+//! [`TransportKind::Deterministic`]: ../coordinator_interface/enum.TransportKind.html#variant.Deterministic
+
+use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender};
+use fproc_sndbx::ipc::generate_random_name;
+use parking_lot::{const_mutex, Mutex};
+use remote_trait_object::transport::{Terminate, TransportError, TransportRecv, TransportSend};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often a blocked receiver checks whether it has been terminated
+const TERMINATION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const PENDING_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Links that the ports can be connected to, by their names
+static LINKS: Mutex<Vec<(String, Arc<Queue>)>> = const_mutex(Vec::new());
+
+struct Queue {
+    /// Messages that have been sent but not delivered yet, along with the end that they are sent to
+    pending: Mutex<VecDeque<(usize, Vec<u8>)>>,
+    /// Channels that deliver the messages to each end
+    delivered: [(Sender<Vec<u8>>, Receiver<Vec<u8>>); 2],
+}
+
+/// A link over [`TransportKind::Deterministic`], which delivers a message only when it is stepped.
+///
+/// Unlike the other transports, an end doesn't wait for the other one on the initialization,
+/// so both ports can be initialized one by one in the thread of the test.
+///
+/// [`TransportKind::Deterministic`]: ../coordinator_interface/enum.TransportKind.html#variant.Deterministic
+pub struct DeterministicLink {
+    name: String,
+    queue: Arc<Queue>,
+}
+
+impl Default for DeterministicLink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeterministicLink {
+    pub fn new() -> Self {
+        let name = generate_random_name();
+        let queue = Arc::new(Queue {
+            pending: Mutex::new(VecDeque::new()),
+            delivered: [channel::unbounded(), channel::unbounded()],
+        });
+        LINKS.lock().push((name.clone(), Arc::clone(&queue)));
+        Self {
+            name,
+            queue,
+        }
+    }
+
+    /// Gives `ipc_arg` for each end of the link, which must be used before the link is dropped.
+    pub fn arguments_for_both_ends(&self) -> (Vec<u8>, Vec<u8>) {
+        (serde_cbor::to_vec(&(&self.name, 0usize)).unwrap(), serde_cbor::to_vec(&(&self.name, 1usize)).unwrap())
+    }
+
+    /// Returns the number of the messages waiting to be delivered.
+    pub fn pending(&self) -> usize {
+        self.queue.pending.lock().len()
+    }
+
+    /// Waits until a message is sent, returning `false` if none has been sent within the `timeout`.
+    pub fn wait_pending(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.pending() == 0 {
+            if Instant::now() >= deadline {
+                return false
+            }
+            std::thread::sleep(PENDING_POLL_INTERVAL);
+        }
+        true
+    }
+
+    /// Delivers the oldest message, returning the end that it has been delivered to.
+    pub fn step(&self) -> Option<usize> {
+        let (end, data) = self.queue.pending.lock().pop_front()?;
+        // The end may have been terminated already, in which case the message is just lost.
+        let _ = self.queue.delivered[end].0.send(data);
+        Some(end)
+    }
+
+    /// Delivers all the messages that are waiting, returning how many have been delivered.
+    ///
+    /// The replies to them are sent a while later, so they may not be delivered yet.
+    pub fn deliver_all(&self) -> usize {
+        let mut delivered = 0;
+        while self.step().is_some() {
+            delivered += 1;
+        }
+        delivered
+    }
+}
+
+impl Drop for DeterministicLink {
+    fn drop(&mut self) {
+        // The ends that have been connected keep the queue.
+        LINKS.lock().retain(|(name, _)| name != &self.name);
+    }
+}
+
+/// Connects to a link as the end given by `ipc_arg`.
+pub fn connect(ipc_arg: &[u8]) -> Result<(DeterministicSend, DeterministicRecv), String> {
+    let (name, end): (String, usize) = serde_cbor::from_slice(ipc_arg).map_err(|err| err.to_string())?;
+    if end > 1 {
+        return Err(format!("Invalid end {} of a deterministic link", end))
+    }
+    let queue = LINKS
+        .lock()
+        .iter()
+        .find(|(link, _)| link == &name)
+        .map(|(_, queue)| Arc::clone(queue))
+        .ok_or_else(|| format!("No deterministic link named {}", name))?;
+
+    let terminated = Arc::new(AtomicBool::new(false));
+    Ok((
+        DeterministicSend {
+            queue: Arc::clone(&queue),
+            to: 1 - end,
+            terminated: Arc::clone(&terminated),
+        },
+        DeterministicRecv {
+            queue,
+            end,
+            terminated,
+        },
+    ))
+}
+
+pub struct DeterministicSend {
+    queue: Arc<Queue>,
+    /// The other end of the link
+    to: usize,
+    terminated: Arc<AtomicBool>,
+}
+
+impl TransportSend for DeterministicSend {
+    fn send(&self, data: &[u8], _timeout: Option<Duration>) -> Result<(), TransportError> {
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(TransportError::Termination)
+        }
+        self.queue.pending.lock().push_back((self.to, data.to_vec()));
+        Ok(())
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+pub struct DeterministicRecv {
+    queue: Arc<Queue>,
+    end: usize,
+    terminated: Arc<AtomicBool>,
+}
+
+impl TransportRecv for DeterministicRecv {
+    fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, TransportError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if self.terminated.load(Ordering::SeqCst) {
+                return Err(TransportError::Termination)
+            }
+            let poll = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(TransportError::TimeOut)
+                    }
+                    std::cmp::min(deadline - now, TERMINATION_POLL_INTERVAL)
+                }
+                None => TERMINATION_POLL_INTERVAL,
+            };
+            match self.queue.delivered[self.end].1.recv_timeout(poll) {
+                Ok(data) => return Ok(data),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Err(TransportError::Custom),
+            }
+        }
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+/// A blocked receiver notices the termination on its next poll.
+struct Terminator {
+    terminated: Arc<AtomicBool>,
+}
+
+impl Terminate for Terminator {
+    fn terminate(&self) {
+        self.terminated.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,755 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::coordinator_interface::TransportKind;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// An error that a module reports back to the coordinator.
+///
+/// Since it is delivered over RTO, it must stay serializable.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ModuleError {
+    /// [`FoundryModule::initialize`] has been called more than once.
+    ///
+    /// [`FoundryModule::initialize`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.initialize
+    AlreadyInitialized,
+    /// [`UserModule::new`] has failed with the given reason.
+    ///
+    /// [`UserModule::new`]: ./trait.UserModule.html#tymethod.new
+    InitializationFailed {
+        message: String,
+    },
+    /// Some of the exports could not be prepared.
+    ///
+    /// The module is still initialized and the rest of the exports are available.
+    ExportPreparationFailed {
+        errors: Vec<ExportError>,
+    },
+    /// The port can't be linked with the requested transport.
+    UnsupportedTransport {
+        transport: TransportKind,
+    },
+    /// The port has failed to establish the transport.
+    TransportFailed {
+        message: String,
+    },
+    /// The user module has panicked while serving the call.
+    Panicked {
+        message: String,
+    },
+    /// The port has failed to send a message larger than [`PartialRtoConfig::max_message_size`],
+    /// which has broken the link.
+    ///
+    /// It is reported by the next [`FoundryModule::call`] or [`FoundryModule::debug`] of the module,
+    /// or by the one that has failed for it.
+    ///
+    /// [`PartialRtoConfig::max_message_size`]: ./coordinator_interface/struct.PartialRtoConfig.html#structfield.max_message_size
+    /// [`FoundryModule::call`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.call
+    /// [`FoundryModule::debug`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.debug
+    MessageTooLarge {
+        name: String,
+        size: usize,
+        max: usize,
+    },
+    /// The user module doesn't serve the method passed to [`FoundryModule::call`].
+    ///
+    /// [`FoundryModule::call`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.call
+    UnknownMethod {
+        method: String,
+    },
+    /// [`FoundryModule::finish_bootstrap`] has been called while the port is not initialized yet.
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.finish_bootstrap
+    PortNotInitialized {
+        name: String,
+    },
+    /// The module has not been initialized, or its initialization has failed.
+    NotInitialized,
+    /// The module has been shut down, and only [`FoundryModule::reinitialize`] brings it back.
+    ///
+    /// [`FoundryModule::reinitialize`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.reinitialize
+    ShutDown,
+    /// [`FoundryModule::reinitialize`] has been called before the module is shut down.
+    ///
+    /// [`FoundryModule::reinitialize`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.reinitialize
+    NotShutDown,
+    /// [`Port::initialize`] has been called on a port that has been linked already.
+    ///
+    /// [`Port::initialize`]: ./coordinator_interface/trait.Port.html#tymethod.initialize
+    PortAlreadyInitialized {
+        name: String,
+    },
+    /// The port has been disconnected by [`Port::disconnect`], so it can't be linked again.
+    ///
+    /// [`Port::disconnect`]: ./coordinator_interface/trait.Port.html#tymethod.disconnect
+    PortDisconnected {
+        name: String,
+    },
+    /// The module already has a port of the name.
+    DuplicatePort {
+        name: String,
+    },
+    /// [`UserModule::can_shutdown`] has rejected the shutdown.
+    ///
+    /// [`UserModule::can_shutdown`]: ./trait.UserModule.html#method.can_shutdown
+    ShutdownRejected,
+    /// The module has been shut down, but the thread serving it has not taken the signal within the timeout.
+    ShutdownSignalTimeout {
+        timeout: Duration,
+    },
+    /// The other end of the link speaks another version of the protocol between the ports.
+    VersionMismatch {
+        ours: u32,
+        theirs: u32,
+    },
+    /// More than one of the exports given to [`FoundryModule::initialize_with_keys`] has the key.
+    ///
+    /// [`FoundryModule::initialize_with_keys`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.initialize_with_keys
+    DuplicateExportKey {
+        key: String,
+    },
+    /// The transport has not been established within [`PartialRtoConfig::init_timeout`].
+    ///
+    /// [`PartialRtoConfig::init_timeout`]: ./coordinator_interface/struct.PartialRtoConfig.html#structfield.init_timeout
+    PortInitTimeout,
+    /// [`FoundryModule::finish_bootstrap`] would clear the services of the keys, which no port has exported,
+    /// under [`ModuleConfig::strict_exports`].
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.finish_bootstrap
+    /// [`ModuleConfig::strict_exports`]: ./struct.ModuleConfig.html#structfield.strict_exports
+    UnexportedServices {
+        keys: Vec<String>,
+    },
+    /// [`FoundryModule::finish_bootstrap`] has been called more than once,
+    /// or [`FoundryModule::create_port`] has been called after it.
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.finish_bootstrap
+    /// [`FoundryModule::create_port`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.create_port
+    BootstrapAlreadyFinished,
+    /// [`FoundryModule::create_dynamic_port`] has been called before [`FoundryModule::finish_bootstrap`].
+    ///
+    /// [`FoundryModule::create_dynamic_port`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.create_dynamic_port
+    /// [`FoundryModule::finish_bootstrap`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.finish_bootstrap
+    BootstrapNotFinished,
+    /// The runtime of [`ModuleConfig::runtime`] has dropped the future of the call before it finished,
+    /// as the runtime has been shut down.
+    ///
+    /// [`ModuleConfig::runtime`]: ./struct.ModuleConfig.html#structfield.runtime
+    CallCancelled,
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::AlreadyInitialized => write!(f, "Module has been initialized twice"),
+            ModuleError::InitializationFailed {
+                message,
+            } => write!(f, "Failed to create the module: {}", message),
+            ModuleError::ExportPreparationFailed {
+                errors,
+            } => {
+                write!(f, "Failed to prepare {} export(s)", errors.len())?;
+                for error in errors {
+                    write!(f, "; {}", error)?;
+                }
+                Ok(())
+            }
+            ModuleError::UnsupportedTransport {
+                transport,
+            } => write!(f, "Transport {:?} is not supported", transport),
+            ModuleError::TransportFailed {
+                message,
+            } => write!(f, "Failed to establish the transport: {}", message),
+            ModuleError::Panicked {
+                message,
+            } => write!(f, "Module has panicked: {}", message),
+            ModuleError::MessageTooLarge {
+                name,
+                size,
+                max,
+            } => write!(
+                f,
+                "Port {} has failed to send a message of {} bytes, over the limit of {} bytes",
+                name, size, max
+            ),
+            ModuleError::UnknownMethod {
+                method,
+            } => write!(f, "Unknown method `{}`", method),
+            ModuleError::PortNotInitialized {
+                name,
+            } => write!(f, "Port {} has not been initialized", name),
+            ModuleError::NotInitialized => write!(f, "Module has not been initialized"),
+            ModuleError::ShutDown => write!(f, "Module has been shut down"),
+            ModuleError::NotShutDown => write!(f, "Module must be shut down to be initialized again"),
+            ModuleError::PortAlreadyInitialized {
+                name,
+            } => write!(f, "Port {} has been initialized already", name),
+            ModuleError::PortDisconnected {
+                name,
+            } => write!(f, "Port {} has been disconnected", name),
+            ModuleError::DuplicatePort {
+                name,
+            } => write!(f, "Port {} already exists", name),
+            ModuleError::ShutdownRejected => write!(f, "Module has rejected to be shut down"),
+            ModuleError::ShutdownSignalTimeout {
+                timeout,
+            } => write!(
+                f,
+                "Module has been shut down, but its serving thread has not taken the signal within {:?}",
+                timeout
+            ),
+            ModuleError::VersionMismatch {
+                ours,
+                theirs,
+            } => write!(f, "Port protocol version {} doesn't match the other end's {}", ours, theirs),
+            ModuleError::DuplicateExportKey {
+                key,
+            } => write!(f, "More than one export has the key `{}`", key),
+            ModuleError::PortInitTimeout => write!(f, "Transport has not been established in time"),
+            ModuleError::UnexportedServices {
+                keys,
+            } => write!(f, "Services {:?} have never been exported", keys),
+            ModuleError::BootstrapAlreadyFinished => write!(f, "Bootstrap has been finished already"),
+            ModuleError::BootstrapNotFinished => write!(f, "Bootstrap has not been finished yet"),
+            ModuleError::CallCancelled => write!(f, "Call has been cancelled by the shutdown of the runtime"),
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+/// An invalid configuration rejected by [`PartialRtoConfigBuilder::build`].
+///
+/// [`PartialRtoConfigBuilder::build`]: ./coordinator_interface/struct.PartialRtoConfigBuilder.html#method.build
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConfigError {
+    /// RTO can't make any call without a slot.
+    ZeroCallSlots,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ZeroCallSlots => write!(f, "call_slots must be greater than 0"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A failure of [`call_with_timeout`].
+///
+/// [`call_with_timeout`]: ./fn.call_with_timeout.html
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CallTimeoutError {
+    /// The call has not returned within the timeout, and is left running.
+    TimedOut,
+    /// As many calls as the limit are still running, so the call has not been made.
+    TooManyOutstanding {
+        limit: usize,
+    },
+}
+
+impl fmt::Display for CallTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallTimeoutError::TimedOut => write!(f, "Call has not returned in time"),
+            CallTimeoutError::TooManyOutstanding {
+                limit,
+            } => write!(f, "{} calls are still running, which is the limit", limit),
+        }
+    }
+}
+
+impl std::error::Error for CallTimeoutError {}
+
+/// A failure of [`debug::call_typed`] and [`debug::call_with_codec`].
+///
+/// [`debug::call_typed`]: ./debug/fn.call_typed.html
+/// [`debug::call_with_codec`]: ./debug/fn.call_with_codec.html
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DebugError {
+    /// The module has failed to serve the call.
+    Module {
+        error: ModuleError,
+    },
+    /// The request or the response couldn't be encoded or decoded by the codec.
+    Codec {
+        message: String,
+    },
+}
+
+impl fmt::Display for DebugError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugError::Module {
+                error,
+            } => write!(f, "{}", error),
+            DebugError::Codec {
+                message,
+            } => write!(f, "Failed to encode or decode the debug call: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DebugError {}
+
+/// An error from looking up a service in the exporting service pool.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PoolError {
+    /// The requested index is beyond the number of services loaded in the pool.
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    /// The pool has been cleared by [`FoundryModule::finish_bootstrap`].
+    ///
+    /// [`FoundryModule::finish_bootstrap`]: ./coordinator_interface/trait.FoundryModule.html#tymethod.finish_bootstrap
+    AlreadyCleared,
+    /// The service in the slot has failed to be prepared at the initialization.
+    NotPrepared {
+        index: usize,
+    },
+    /// The service has failed to be prepared on its first export in the lazy mode.
+    PreparationFailed {
+        error: ExportError,
+    },
+    /// No service has been prepared with the constructor name.
+    UnknownName {
+        name: String,
+    },
+    /// More than one service has been prepared with the constructor name.
+    AmbiguousName {
+        name: String,
+    },
+    /// The module has no service made at runtime for the key.
+    UnknownDynamicExport {
+        key: String,
+    },
+    /// No service has been given the key at the initialization.
+    UnknownKey {
+        key: String,
+    },
+    /// The module has already been shut down.
+    ModuleGone,
+    /// The port has not been initialized, so it has no link to export the service through.
+    PortNotInitialized,
+    /// The port has been disconnected by [`Port::disconnect`].
+    ///
+    /// [`Port::disconnect`]: ./coordinator_interface/trait.Port.html#tymethod.disconnect
+    Disconnected,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::IndexOutOfRange {
+                index,
+                len,
+            } => write!(f, "Export index {} is out of range for a pool of {} services", index, len),
+            PoolError::AlreadyCleared => write!(f, "Exporting service pool has already been cleared"),
+            PoolError::NotPrepared {
+                index,
+            } => write!(f, "Service {} has failed to be prepared", index),
+            PoolError::PreparationFailed {
+                error,
+            } => write!(f, "{}", error),
+            PoolError::UnknownName {
+                name,
+            } => write!(f, "No service has been prepared with `{}`", name),
+            PoolError::AmbiguousName {
+                name,
+            } => write!(f, "More than one service has been prepared with `{}`", name),
+            PoolError::UnknownDynamicExport {
+                key,
+            } => write!(f, "No service has been made at runtime for `{}`", key),
+            PoolError::UnknownKey {
+                key,
+            } => write!(f, "No service has been given the key `{}`", key),
+            PoolError::ModuleGone => write!(f, "Module has already been shut down"),
+            PoolError::PortNotInitialized => write!(f, "Port has not been initialized to export through"),
+            PoolError::Disconnected => write!(f, "Port has been disconnected, so nothing can be exported through it"),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/// A failure of [`UserModule::prepare_service_to_export`] for an entry of the exports.
+///
+/// [`UserModule::prepare_service_to_export`]: ./trait.UserModule.html#tymethod.prepare_service_to_export
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportError {
+    /// Index of the entry in the `exports` given to the initialization
+    pub index: usize,
+    pub ctor_name: String,
+    pub message: String,
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to prepare export {} with `{}`: {}", self.index, self.ctor_name, self.message)
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// A failure of importing a service given to [`Port::import`].
+///
+/// [`Port::import`]: ./coordinator_interface/trait.Port.html#tymethod.import
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ImportError {
+    /// The module has refused to import the service.
+    Rejected {
+        name: String,
+        message: String,
+    },
+    /// The module has panicked while importing the service.
+    Panicked {
+        name: String,
+        message: String,
+    },
+    /// The module has already been shut down.
+    ModuleGone,
+    /// The port has not been initialized, so it has no link to import the service from.
+    PortNotInitialized,
+    /// The port has been disconnected by [`Port::disconnect`].
+    ///
+    /// [`Port::disconnect`]: ./coordinator_interface/trait.Port.html#tymethod.disconnect
+    Disconnected,
+    /// Some of the slots have failed to be imported, so none of them is left imported by [`Port::import`].
+    ///
+    /// [`Port::import`]: ./coordinator_interface/trait.Port.html#tymethod.import
+    Failed {
+        errors: Vec<ImportError>,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Rejected {
+                name,
+                message,
+            } => write!(f, "Module has rejected to import `{}`: {}", name, message),
+            ImportError::Panicked {
+                name,
+                message,
+            } => write!(f, "Module has panicked while importing `{}`: {}", name, message),
+            ImportError::ModuleGone => write!(f, "Module has already been shut down"),
+            ImportError::PortNotInitialized => write!(f, "Port has not been initialized to import from"),
+            ImportError::Disconnected => write!(f, "Port has been disconnected, so nothing can be imported from it"),
+            ImportError::Failed {
+                errors,
+            } => {
+                write!(f, "Failed to import {} service(s), so none has been imported", errors.len())?;
+                for error in errors {
+                    write!(f, "; {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// A failure of linking modules with [`link_two`], [`link_all`] or [`link_mesh`], along with the name of the module that has failed.
+///
+/// [`link_two`]: ./fn.link_two.html
+/// [`link_all`]: ./fn.link_all.html
+/// [`link_mesh`]: ./fn.link_mesh.html
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LinkError {
+    Initialize {
+        module: String,
+        error: ModuleError,
+    },
+    Export {
+        module: String,
+        error: PoolError,
+    },
+    Import {
+        module: String,
+        error: ImportError,
+    },
+    /// The topology given to [`link_mesh`] doesn't fit the module.
+    ///
+    /// `module` is empty if it doesn't fit the modules as a whole.
+    ///
+    /// [`link_mesh`]: ./fn.link_mesh.html
+    Topology {
+        module: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Initialize {
+                module,
+                error,
+            } => write!(f, "Failed to initialize the port of {}: {}", module, error),
+            LinkError::Export {
+                module,
+                error,
+            } => write!(f, "Failed to export from {}: {}", module, error),
+            LinkError::Import {
+                module,
+                error,
+            } => write!(f, "Failed to import into {}: {}", module, error),
+            LinkError::Topology {
+                module,
+                message,
+            } => write!(f, "Invalid topology for {}: {}", module, message),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Any failure of this crate, for the callers that would rather handle a single enum.
+///
+/// The common failures have their own variants regardless of the API that has returned them,
+/// while the rest keep the error of the API, which is given back as its `source()`.
+/// The RTO interfaces keep returning the specific ones.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ModuleRtError {
+    /// The module has been initialized twice.
+    AlreadyInitialized,
+    /// The module already has a port of the name.
+    DuplicatePort {
+        name: String,
+    },
+    /// The port has not been initialized, which is named if the module has told it.
+    PortNotInitialized {
+        name: Option<String>,
+    },
+    /// The requested index is beyond the number of services loaded in the pool.
+    ExportIndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    /// A service given to [`Port::import`] has failed to be imported.
+    ///
+    /// [`Port::import`]: ./coordinator_interface/trait.Port.html#tymethod.import
+    ImportFailed {
+        error: ImportError,
+    },
+    /// The module has already been shut down.
+    ModuleGone,
+    /// The user module has panicked while serving the call.
+    Panicked {
+        message: String,
+    },
+    /// The other end of the link speaks another version of the protocol between the ports.
+    VersionMismatch {
+        ours: u32,
+        theirs: u32,
+    },
+    /// Any other failure of the module or one of its ports
+    Module {
+        error: ModuleError,
+    },
+    /// Any other failure of exporting a service
+    Export {
+        error: PoolError,
+    },
+    Link {
+        error: LinkError,
+    },
+    Config {
+        error: ConfigError,
+    },
+    /// The request or the response of a debug call couldn't be encoded or decoded by the codec.
+    Codec {
+        message: String,
+    },
+}
+
+impl fmt::Display for ModuleRtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleRtError::AlreadyInitialized => write!(f, "Module has been initialized twice"),
+            ModuleRtError::DuplicatePort {
+                name,
+            } => write!(f, "Port {} already exists", name),
+            ModuleRtError::PortNotInitialized {
+                name: Some(name),
+            } => write!(f, "Port {} has not been initialized", name),
+            ModuleRtError::PortNotInitialized {
+                name: None,
+            } => write!(f, "Port has not been initialized"),
+            ModuleRtError::ExportIndexOutOfRange {
+                index,
+                len,
+            } => write!(f, "Export index {} is out of range for a pool of {} services", index, len),
+            ModuleRtError::ImportFailed {
+                error,
+            } => write!(f, "Failed to import a service: {}", error),
+            ModuleRtError::ModuleGone => write!(f, "Module has already been shut down"),
+            ModuleRtError::Panicked {
+                message,
+            } => write!(f, "Module has panicked: {}", message),
+            ModuleRtError::VersionMismatch {
+                ours,
+                theirs,
+            } => write!(f, "Port protocol version {} doesn't match the other end's {}", ours, theirs),
+            // The details of the rest are left to the source.
+            ModuleRtError::Module {
+                ..
+            } => write!(f, "Module has failed"),
+            ModuleRtError::Export {
+                ..
+            } => write!(f, "Failed to export a service"),
+            ModuleRtError::Link {
+                ..
+            } => write!(f, "Failed to link the modules"),
+            ModuleRtError::Config {
+                ..
+            } => write!(f, "Invalid configuration"),
+            ModuleRtError::Codec {
+                message,
+            } => write!(f, "Failed to encode or decode the debug call: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ModuleRtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModuleRtError::ImportFailed {
+                error,
+            } => Some(error),
+            ModuleRtError::Module {
+                error,
+            } => Some(error),
+            ModuleRtError::Export {
+                error,
+            } => Some(error),
+            ModuleRtError::Link {
+                error,
+            } => Some(error),
+            ModuleRtError::Config {
+                error,
+            } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ModuleError> for ModuleRtError {
+    fn from(error: ModuleError) -> Self {
+        match error {
+            ModuleError::AlreadyInitialized => ModuleRtError::AlreadyInitialized,
+            ModuleError::DuplicatePort {
+                name,
+            } => ModuleRtError::DuplicatePort {
+                name,
+            },
+            ModuleError::PortNotInitialized {
+                name,
+            } => ModuleRtError::PortNotInitialized {
+                name: Some(name),
+            },
+            ModuleError::ShutDown => ModuleRtError::ModuleGone,
+            ModuleError::Panicked {
+                message,
+            } => ModuleRtError::Panicked {
+                message,
+            },
+            ModuleError::VersionMismatch {
+                ours,
+                theirs,
+            } => ModuleRtError::VersionMismatch {
+                ours,
+                theirs,
+            },
+            error => ModuleRtError::Module {
+                error,
+            },
+        }
+    }
+}
+
+impl From<PoolError> for ModuleRtError {
+    fn from(error: PoolError) -> Self {
+        match error {
+            PoolError::IndexOutOfRange {
+                index,
+                len,
+            } => ModuleRtError::ExportIndexOutOfRange {
+                index,
+                len,
+            },
+            PoolError::ModuleGone => ModuleRtError::ModuleGone,
+            PoolError::PortNotInitialized => ModuleRtError::PortNotInitialized {
+                name: None,
+            },
+            error => ModuleRtError::Export {
+                error,
+            },
+        }
+    }
+}
+
+impl From<ImportError> for ModuleRtError {
+    fn from(error: ImportError) -> Self {
+        match error {
+            ImportError::ModuleGone => ModuleRtError::ModuleGone,
+            ImportError::PortNotInitialized => ModuleRtError::PortNotInitialized {
+                name: None,
+            },
+            error => ModuleRtError::ImportFailed {
+                error,
+            },
+        }
+    }
+}
+
+impl From<LinkError> for ModuleRtError {
+    fn from(error: LinkError) -> Self {
+        ModuleRtError::Link {
+            error,
+        }
+    }
+}
+
+impl From<ConfigError> for ModuleRtError {
+    fn from(error: ConfigError) -> Self {
+        ModuleRtError::Config {
+            error,
+        }
+    }
+}
+
+impl From<DebugError> for ModuleRtError {
+    fn from(error: DebugError) -> Self {
+        match error {
+            DebugError::Module {
+                error,
+            } => error.into(),
+            DebugError::Codec {
+                message,
+            } => ModuleRtError::Codec {
+                message,
+            },
+        }
+    }
+}
@@ -16,10 +16,49 @@
 
 extern crate foundry_process_sandbox as fproc_sndbx;
 
+#[macro_use]
+mod trace;
+
 mod bootstrap;
+mod codec;
+mod compression;
+mod config;
 pub mod coordinator_interface;
+pub mod debug;
+#[cfg(feature = "testing")]
+mod deterministic;
+mod error;
+mod limit;
+mod link;
+#[cfg(feature = "prometheus")]
+pub mod metrics;
 mod module;
+#[cfg(windows)]
+mod named_pipe;
+mod panic_hook;
 mod port;
+#[cfg(unix)]
+mod shared_memory;
+mod stats;
+mod tcp;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod timeout;
 
-pub use bootstrap::{create_foundry_module, start};
-pub use module::UserModule;
+#[cfg(feature = "tokio")]
+pub use bootstrap::start_async;
+pub use bootstrap::{
+    create_foundry_module, create_foundry_module_with_config, create_foundry_module_with_pool, start, start_detached,
+    start_with_config, start_with_handle, ModuleHandle, ShutdownWaiter,
+};
+pub use codec::{Cbor, ModuleCodec};
+pub use config::{ModuleConfig, ModuleEvent, PanicHook, RtoConfigHook};
+pub use error::{
+    CallTimeoutError, ConfigError, DebugError, ExportError, ImportError, LinkError, ModuleError, ModuleRtError,
+    PoolError,
+};
+pub use link::{dry_link, link_all, link_mesh, link_two, LinkEnd};
+#[cfg(feature = "tokio")]
+pub use module::CallFuture;
+pub use module::{Ping, ThreadPoolHandle, TypedDebug, TypedInit, UserModule};
+pub use timeout::{call_with_timeout, MAX_OUTSTANDING_CALLS};
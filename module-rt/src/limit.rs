@@ -0,0 +1,68 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A limit on the size of the messages that a port sends, applied on top of its transport.
+
+use parking_lot::Mutex;
+use remote_trait_object::transport::{Terminate, TransportError, TransportSend};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A message that has been rejected for its size
+#[derive(Clone, Copy, Debug)]
+pub struct Oversized {
+    pub size: usize,
+    pub max: usize,
+}
+
+/// Where the last rejected message is kept for the port to report, as RTO doesn't carry the reason.
+pub type OversizedSlot = Arc<Mutex<Option<Oversized>>>;
+
+/// Wraps the sending end of a transport to reject a message larger than `max_message_size`, if given,
+/// recording it in `oversized`.
+pub fn wrap<S: TransportSend>(send: S, max_message_size: Option<usize>, oversized: OversizedSlot) -> LimitedSend<S> {
+    LimitedSend {
+        inner: send,
+        max_message_size,
+        oversized,
+    }
+}
+
+pub struct LimitedSend<S> {
+    inner: S,
+    max_message_size: Option<usize>,
+    oversized: OversizedSlot,
+}
+
+impl<S: TransportSend> TransportSend for LimitedSend<S> {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        if let Some(max_message_size) = self.max_message_size {
+            if data.len() > max_message_size {
+                log::error!("Message of {} bytes exceeds max_message_size of {} bytes", data.len(), max_message_size);
+                *self.oversized.lock() = Some(Oversized {
+                    size: data.len(),
+                    max: max_message_size,
+                });
+                return Err(TransportError::Custom)
+            }
+        }
+        self.inner.send(data, timeout)
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        self.inner.create_terminator()
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#[cfg(unix)]
+use crate::coordinator_interface::SharedMemoryEnd;
+use crate::coordinator_interface::{FoundryModule, NamedPipeEnd, PartialRtoConfig, Port, TcpEnd, TransportKind};
+use crate::error::{ImportError, LinkError, PoolError};
+#[cfg(unix)]
+use fproc_sndbx::ipc::unix_socket::DomainSocket;
+use fproc_sndbx::ipc::{generate_random_name, intra::Intra, Ipc};
+use remote_trait_object::{Config as RtoConfig, Context as RtoContext, ServiceRef, ServiceToExport, ServiceToImport};
+use std::net::{Ipv4Addr, TcpListener};
+
+/// One side of a link: the name of the module, the module, and the services to export to the other side.
+///
+/// Each export is an index of the exporting service pool with the name that the other side imports it with.
+pub type LinkEnd<'a> = (&'a str, &'a mut dyn FoundryModule, &'a [(usize, String)]);
+
+/// Links two modules, doing what the coordinator does for a link.
+///
+/// It creates a port on each module named after the other side, initializes both ends over `transport`,
+/// and exports the services of each side into the other.
+/// [`TransportKind::Tcp`] is linked over the loopback interface.
+///
+/// The modules may be remote ones, driven through RTO, or local ones made by [`create_foundry_module`].
+///
+/// [`TransportKind::Tcp`]: ./coordinator_interface/enum.TransportKind.html#variant.Tcp
+/// [`create_foundry_module`]: ./fn.create_foundry_module.html
+pub fn link_two(
+    (name_a, module_a, exports_a): LinkEnd,
+    (name_b, module_b, exports_b): LinkEnd,
+    transport: TransportKind,
+) -> Result<(), LinkError> {
+    let create_port = |name: &str, module: &mut dyn FoundryModule, peer: &str| -> Result<LinkedPort, LinkError> {
+        let port = module.create_port(peer).map_err(|error| LinkError::Initialize {
+            module: name.to_owned(),
+            error,
+        })?;
+        Ok(LinkedPort::new(port))
+    };
+    let mut port_a = create_port(name_a, &mut *module_a, name_b)?;
+    let mut port_b = create_port(name_b, &mut *module_b, name_a)?;
+    let (ipc_arg_a, ipc_arg_b) = arguments_for_both_ends(transport);
+
+    // Both ends must be initialized at the same time, since each waits for the other.
+    let mut proxy_a = port_a.port;
+    let join = std::thread::spawn(move || {
+        let result = proxy_a.initialize(default_rto_config(), ipc_arg_a, transport);
+        (proxy_a, result)
+    });
+    let result_b = port_b.port.initialize(default_rto_config(), ipc_arg_b, transport);
+    let (proxy_a, result_a) = join.join().unwrap();
+    port_a.port = proxy_a;
+    result_a.map_err(|error| LinkError::Initialize {
+        module: name_a.to_owned(),
+        error,
+    })?;
+    result_b.map_err(|error| LinkError::Initialize {
+        module: name_b.to_owned(),
+        error,
+    })?;
+
+    exchange((name_a, &mut *port_a.port, exports_a), (name_b, &mut *port_b.port))?;
+    exchange((name_b, &mut *port_b.port, exports_b), (name_a, &mut *port_a.port))
+}
+
+/// A port to link, driven through RTO whether its module is remote or local.
+struct LinkedPort {
+    port: Box<dyn Port>,
+    /// Both sides of the in-process link that serves the port of a local module, dropped after `port`
+    _bridge: Option<(RtoContext, RtoContext)>,
+}
+
+impl LinkedPort {
+    fn new(port: ServiceRef<dyn Port>) -> Self {
+        match port {
+            ServiceRef::Import(port) => Self {
+                port: port.into_proxy(),
+                _bridge: None,
+            },
+            // A local module hands over the port itself, which is served as a module is by `start()`.
+            ServiceRef::Export(port) => {
+                let (port, bridge) = serve_port(port);
+                Self {
+                    port,
+                    _bridge: Some(bridge),
+                }
+            }
+        }
+    }
+}
+
+/// Serves the port over an in-process link, returning the proxy to it along with both sides of the link.
+fn serve_port(port: ServiceToExport<dyn Port>) -> (Box<dyn Port>, (RtoContext, RtoContext)) {
+    let (ipc_arg_export, ipc_arg_import) = Intra::arguments_for_both_ends();
+    let join = std::thread::spawn(move || {
+        let (transport_send, transport_recv) = Intra::new(ipc_arg_export).split();
+        RtoContext::with_initial_service_export(RtoConfig::default_setup(), transport_send, transport_recv, port)
+    });
+    let (transport_send, transport_recv) = Intra::new(ipc_arg_import).split();
+    let (import_context, port): (_, ServiceToImport<dyn Port>) =
+        RtoContext::with_initial_service_import(RtoConfig::default_setup(), transport_send, transport_recv);
+    let export_context = join.join().unwrap();
+    (port.into_proxy(), (export_context, import_context))
+}
+
+/// Links every pair of the modules with [`link_two`].
+///
+/// `exports(i, j)` gives what the `i`-th module exports to the `j`-th module.
+/// It doesn't finish the bootstrap, so more links can be made before that.
+pub fn link_all(
+    modules: &mut [(&str, &mut dyn FoundryModule)],
+    exports: impl Fn(usize, usize) -> Vec<(usize, String)>,
+    transport: TransportKind,
+) -> Result<(), LinkError> {
+    for j in 0..modules.len() {
+        for i in 0..j {
+            let (left, right) = modules.split_at_mut(j);
+            let (name_a, module_a) = &mut left[i];
+            let (name_b, module_b) = &mut right[0];
+            link_two(
+                (*name_a, &mut **module_a, &exports(i, j)),
+                (*name_b, &mut **module_b, &exports(j, i)),
+                transport,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks what [`link_all`] would do with the same arguments, without creating any port or opening any transport.
+///
+/// It catches the modules given the same name, which would end up with duplicate ports,
+/// the exports out of the range of the exporting module, reported as [`link_all`] would,
+/// and a name imported from more than one service of the same module.
+/// Whether each service can be prepared and imported is still up to the modules, which only the real link finds out.
+/// The modules are only read, and nothing is prepared for the transport, not even a TCP port.
+pub fn dry_link(
+    modules: &[(&str, &dyn FoundryModule)],
+    exports: impl Fn(usize, usize) -> Vec<(usize, String)>,
+) -> Result<(), LinkError> {
+    for (j, (name_b, _)) in modules.iter().enumerate() {
+        if modules[..j].iter().any(|(name_a, _)| name_a == name_b) {
+            return Err(LinkError::Topology {
+                module: (*name_b).to_owned(),
+                message: "Module name is given more than once".to_owned(),
+            })
+        }
+    }
+    for (i, (exporter_name, exporter)) in modules.iter().enumerate() {
+        let len = exporter.exported_count();
+        for (j, (importer_name, _)) in modules.iter().enumerate().filter(|(j, _)| *j != i) {
+            let slots = exports(i, j);
+            for (k, (index, name)) in slots.iter().enumerate() {
+                if *index >= len {
+                    return Err(LinkError::Export {
+                        module: (*exporter_name).to_owned(),
+                        error: PoolError::IndexOutOfRange {
+                            index: *index,
+                            len,
+                        },
+                    })
+                }
+                if slots[..k].iter().any(|(other, imported)| imported == name && other != index) {
+                    return Err(LinkError::Import {
+                        module: (*importer_name).to_owned(),
+                        error: ImportError::Rejected {
+                            name: name.clone(),
+                            message: format!("Name is imported from more than one service of {}", exporter_name),
+                        },
+                    })
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Links every pair of the modules, where each module exports each of its services to exactly one of the others.
+///
+/// `topology[i]` lists the services that the `i`-th module exports to each of the others in their order,
+/// skipping itself, so that it must have an entry for every other module.
+/// Every service loaded by the module must appear exactly once, and each is imported with the name of the exporting module.
+/// A topology that breaks any of these is rejected with [`LinkError::Topology`] before anything is linked.
+///
+/// [`LinkError::Topology`]: ./enum.LinkError.html#variant.Topology
+pub fn link_mesh(
+    modules: &mut [(&str, &mut dyn FoundryModule)],
+    topology: &[Vec<usize>],
+    transport: TransportKind,
+) -> Result<(), LinkError> {
+    if topology.len() != modules.len() {
+        return Err(LinkError::Topology {
+            module: String::new(),
+            message: format!("{} modules are given with a topology for {}", modules.len(), topology.len()),
+        })
+    }
+    for ((name, module), exports) in modules.iter().zip(topology) {
+        validate_exports(exports, modules.len() - 1, module.exported_count()).map_err(|message| {
+            LinkError::Topology {
+                module: (*name).to_owned(),
+                message,
+            }
+        })?;
+    }
+
+    let names: Vec<String> = modules.iter().map(|(name, _)| (*name).to_owned()).collect();
+    link_all(
+        modules,
+        |i, j| {
+            let peer = if j > i {
+                j - 1
+            } else {
+                j
+            };
+            vec![(topology[i][peer], names[i].clone())]
+        },
+        transport,
+    )
+}
+
+/// Checks that the services are exported one to each peer, covering all of them without a duplicate.
+fn validate_exports(exports: &[usize], peers: usize, services: usize) -> Result<(), String> {
+    if exports.len() != peers {
+        return Err(format!("{} exports are given for {} peers", exports.len(), peers))
+    }
+    let mut exported = vec![false; services];
+    for &index in exports {
+        match exported.get_mut(index) {
+            None => return Err(format!("Export index {} is out of range for {} services", index, services)),
+            Some(true) => return Err(format!("Service {} is exported more than once", index)),
+            Some(exported) => *exported = true,
+        }
+    }
+    if let Some(index) = exported.iter().position(|exported| !exported) {
+        return Err(format!("Service {} is not exported", index))
+    }
+    Ok(())
+}
+
+fn exchange(
+    (exporter_name, exporter, exports): (&str, &mut dyn Port, &[(usize, String)]),
+    (importer_name, importer): (&str, &mut dyn Port),
+) -> Result<(), LinkError> {
+    let ids: Vec<usize> = exports.iter().map(|(id, _)| *id).collect();
+    let handles = exporter.export(&ids).map_err(|error| LinkError::Export {
+        module: exporter_name.to_owned(),
+        error,
+    })?;
+    let slots: Vec<_> = exports.iter().map(|(_, name)| name.clone()).zip(handles).collect();
+    importer.import(&slots).map_err(|error| LinkError::Import {
+        module: importer_name.to_owned(),
+        error,
+    })?;
+    Ok(())
+}
+
+/// Size of the largest message that goes through the segment of [`TransportKind::SharedMemory`]
+///
+/// [`TransportKind::SharedMemory`]: ./coordinator_interface/enum.TransportKind.html#variant.SharedMemory
+#[cfg(unix)]
+const SHARED_MEMORY_CAPACITY: usize = 16 << 20;
+
+fn default_rto_config() -> PartialRtoConfig {
+    PartialRtoConfig::builder().build().unwrap()
+}
+
+fn arguments_for_both_ends(transport: TransportKind) -> (Vec<u8>, Vec<u8>) {
+    match transport {
+        TransportKind::Intra => Intra::arguments_for_both_ends(),
+        #[cfg(unix)]
+        TransportKind::DomainSocket => DomainSocket::arguments_for_both_ends(),
+        TransportKind::Tcp => {
+            // Takes a free port, which is released for the listening end.
+            let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap();
+            TcpEnd::arguments_for_both_ends(addr)
+        }
+        TransportKind::NamedPipe => NamedPipeEnd::arguments_for_both_ends(&generate_random_name()),
+        // The link must be owned by the test to be stepped, so the ports fail to find one.
+        TransportKind::Deterministic => (Vec::new(), Vec::new()),
+        #[cfg(unix)]
+        TransportKind::SharedMemory => SharedMemoryEnd::arguments_for_both_ends(
+            std::env::temp_dir().join(generate_random_name()),
+            SHARED_MEMORY_CAPACITY,
+        ),
+        // The ports fail with `UnsupportedTransport` without looking at them.
+        #[cfg(not(unix))]
+        TransportKind::DomainSocket | TransportKind::SharedMemory => (Vec::new(), Vec::new()),
+    }
+}
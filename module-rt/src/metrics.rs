@@ -0,0 +1,145 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An HTTP endpoint that exposes [`FoundryModule::metrics`] to be scraped by Prometheus, only with the `prometheus` feature.
+//!
+//! [`FoundryModule::metrics`]: ../coordinator_interface/trait.FoundryModule.html#tymethod.metrics
+
+use crate::coordinator_interface::{FoundryModule, ModuleMetrics, PortStats};
+use parking_lot::RwLock;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long a scrape may take to send its request or to take the response,
+/// so that an idle connection doesn't hold the endpoint, nor its drop, forever.
+pub const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The endpoint started by [`serve`], which stops when it is dropped.
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// The address that the endpoint listens on, which tells the port chosen by the OS if it has been given 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes up the listener waiting for a connection.
+        let _ = TcpStream::connect(self.local_addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Serves the metrics of `module` in the text format of Prometheus, on any path of `addr`.
+///
+/// Each scrape takes a fresh snapshot, with the read lock of the module held only while taking it.
+/// The requests are answered one by one in a thread of the endpoint,
+/// and a connection is given up after [`SCRAPE_TIMEOUT`] without any progress.
+pub fn serve(addr: impl ToSocketAddrs, module: Arc<RwLock<dyn FoundryModule>>) -> io::Result<MetricsServer> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let stopped_ = Arc::clone(&stopped);
+    let thread = std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            if stopped_.load(Ordering::SeqCst) {
+                break
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::warn!("Failed to accept a scrape: {}", err);
+                    continue
+                }
+            };
+            let (metrics, inflight_calls) = {
+                let module = module.read();
+                (module.metrics(), module.inflight_calls())
+            };
+            if let Err(err) = respond(stream, &render(&metrics, inflight_calls)) {
+                log::warn!("Failed to answer a scrape: {}", err);
+            }
+        }
+    });
+    Ok(MetricsServer {
+        local_addr,
+        stopped,
+        thread: Some(thread),
+    })
+}
+
+fn respond(stream: TcpStream, body: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+    // The request is read up to its end, while what it asks for doesn't matter.
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn render(metrics: &ModuleMetrics, inflight_calls: usize) -> String {
+    let mut body = String::new();
+    let mut gauge = |name: &str, help: &str, value: String| {
+        writeln!(body, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value).unwrap();
+    };
+    gauge("module_uptime_seconds", "Time since the module has been created", metrics.uptime.as_secs_f64().to_string());
+    gauge("module_inflight_calls", "Calls being served or waiting for a worker", inflight_calls.to_string());
+    gauge("module_exported_services", "Services exported through all the ports", metrics.exported_count.to_string());
+    gauge("module_imported_services", "Services imported through all the ports", metrics.imported_count.to_string());
+
+    let counters: [(&str, &str, fn(&PortStats) -> usize); 4] = [
+        ("module_port_sent_messages_total", "Messages sent through the port", |stats| stats.sent_messages),
+        ("module_port_received_messages_total", "Messages received through the port", |stats| stats.received_messages),
+        ("module_port_sent_bytes_total", "Bytes sent through the port", |stats| stats.sent_bytes),
+        ("module_port_received_bytes_total", "Bytes received through the port", |stats| stats.received_bytes),
+    ];
+    for (name, help, value) in counters.iter() {
+        writeln!(body, "# HELP {} {}\n# TYPE {} counter", name, help, name).unwrap();
+        for (port, stats) in &metrics.ports {
+            writeln!(body, "{}{{port=\"{}\"}} {}", name, escape_label(port), value(stats)).unwrap();
+        }
+    }
+    body
+}
+
+/// Escapes a label value as the text format requires.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
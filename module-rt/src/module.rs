@@ -14,8 +14,30 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::codec::{Cbor, ModuleCodec};
+use crate::error::{ImportError, ModuleError};
+use parking_lot::Mutex;
 use remote_trait_object::raw_exchange::{HandleToExchange, Skeleton};
 use remote_trait_object::Context as RtoContext;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+use std::sync::Arc;
+use threadpool::ThreadPool;
+
+/// A check of a link given by [`UserModule::ping_imports`].
+///
+/// [`UserModule::ping_imports`]: ./trait.UserModule.html#method.ping_imports
+pub type Ping<'a> = Box<dyn FnOnce() + 'a>;
+
+/// A call served asynchronously by [`UserModule::call_async`].
+///
+/// [`UserModule::call_async`]: ./trait.UserModule.html#method.call_async
+#[cfg(feature = "tokio")]
+pub type CallFuture = Pin<Box<dyn Future<Output = Result<Vec<u8>, ModuleError>> + Send>>;
 
 /// A trait that represents set of methods that the user must implement to construct a
 /// a working foundry module.
@@ -26,7 +48,11 @@ use remote_trait_object::Context as RtoContext;
 /// [`start`]: ../fn.start.html
 pub trait UserModule: Send {
     /// Creates an instance of module from arguments.
-    fn new(arg: &[u8]) -> Self;
+    ///
+    /// A returned error will be delivered to the coordinator as the result of the initialization.
+    fn new(arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>>
+    where
+        Self: Sized;
 
     /// Creates a service object from the constructor and arguments.
     ///
@@ -34,21 +60,270 @@ pub trait UserModule: Send {
     /// Created `Skeleton`s will be stored in a pool and will be exported to other modules in the export & import phase.
     ///
     /// You have to use `remote-trait-object::raw_exchange` module to convert a trait object into `Skeleton`.
-    fn prepare_service_to_export(&mut self, ctor_name: &str, ctor_arg: &[u8]) -> Skeleton;
+    /// A failure here doesn't abort the initialization; the entry will be left unavailable and reported to the coordinator.
+    ///
+    /// A service that the module keeps using itself can be shared by `Arc<dyn Trait>` or `Arc<RwLock<dyn Trait>>`
+    /// instead of `Box<dyn Trait>`, with a clone of the `Arc` kept by the module.
+    /// Then the service outlives its skeleton, which is dropped when the pool is cleared at the end of the bootstrap
+    /// and when the registry of RTO is cleared on shutdown.
+    /// So it must not hold the proxies imported by the module, which would be left alive after their links are torn down.
+    ///
+    /// The methods of the service are dispatched by RTO, which the runtime can't hook into.
+    /// So a panic in them is not reported to the caller, who waits until the call times out;
+    /// catch it in the method and return an error as a part of the response instead.
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Hands over a service that the module has made at runtime, to be exported by [`Port::export_skeleton`].
+    ///
+    /// Unlike the services prepared from the `exports` of the initialization, these can be exported at any time,
+    /// even after the bootstrap. `key` is whatever the module and the coordinator have agreed on.
+    /// The default implementation has nothing to export.
+    ///
+    /// [`Port::export_skeleton`]: ../coordinator_interface/trait.Port.html#tymethod.export_skeleton
+    fn take_dynamic_export(&mut self, _key: &str) -> Option<Skeleton> {
+        None
+    }
 
     /// Imports a service from its handle.
     ///
     /// This method will be called for every entries specified in link-desc's `import` field, with given name.
     /// Given `handle` could be from any of modules that this module is linked with,
     /// and it is identified by `rto_context` that such link corresponds to.
+    /// `exporter_module` is the name of the module that has exported the service,
+    /// which is the port name that the coordinator has passed to [`FoundryModule::create_port`].
     ///
     /// You have to use `remote-trait-object::raw_exchange` module to convert `HandleToExchange` into a proxy object.
     /// It will require `rto_context` because such conversion must be done on a speicific link.
-    fn import_service(&mut self, rto_context: &RtoContext, name: &str, handle: HandleToExchange);
+    ///
+    /// Return [`ImportError::Rejected`] for a name that the module doesn't know what to do with,
+    /// so that the coordinator finds the misconfigured link in the result of [`Port::import`].
+    ///
+    /// [`ImportError::Rejected`]: ./enum.ImportError.html#variant.Rejected
+    /// [`Port::import`]: ../coordinator_interface/trait.Port.html#tymethod.import
+    /// [`FoundryModule::create_port`]: ../coordinator_interface/trait.FoundryModule.html#tymethod.create_port
+    fn import_service(
+        &mut self,
+        rto_context: &RtoContext,
+        exporter_module: &str,
+        name: &str,
+        handle: HandleToExchange,
+    ) -> Result<(), ImportError>;
+
+    /// Checks whether the module accepts a service under `name`, before any of the slots given to [`Port::import`] is imported.
+    ///
+    /// A slot rejected here fails the whole import with nothing imported, so the module is never left half-imported by it.
+    /// It can't tell whether the handle works, which only [`import_service`](#tymethod.import_service) finds out.
+    /// The default implementation accepts every name.
+    ///
+    /// [`Port::import`]: ../coordinator_interface/trait.Port.html#tymethod.import
+    fn validate_import(&self, _exporter_module: &str, _name: &str) -> Result<(), ImportError> {
+        Ok(())
+    }
+
+    /// Gives another name to a service that has already been imported under `name` from the same module.
+    ///
+    /// A handle can be imported only once, so if the same handle appears more than once in the slots given to [`Port::import`],
+    /// only the first is passed to [`import_service`](#tymethod.import_service) and the rest come here,
+    /// expecting the module to share the proxy under `alias`.
+    /// The default implementation rejects the alias.
+    ///
+    /// [`Port::import`]: ../coordinator_interface/trait.Port.html#tymethod.import
+    fn alias_import(&mut self, _exporter_module: &str, _name: &str, alias: &str) -> Result<(), ImportError> {
+        Err(ImportError::Rejected {
+            name: alias.to_owned(),
+            message: "Module doesn't import a service under more than one name".to_owned(),
+        })
+    }
+
+    /// Drops the service imported under `name`, or the alias given by [`alias_import`](#method.alias_import),
+    /// as a later slot given to [`Port::import`] has failed and the whole import is undone.
+    ///
+    /// The slots are revoked in the reverse order of their imports.
+    /// The default implementation does nothing, leaving the services that have been imported before the failure
+    /// in the module, while the port reports none of them.
+    ///
+    /// [`Port::import`]: ../coordinator_interface/trait.Port.html#tymethod.import
+    fn revoke_import(&mut self, _exporter_module: &str, _name: &str) {}
+
+    /// Tells how many of the services given to [`import_service`](#tymethod.import_service) the module holds,
+    /// so that the coordinator can verify the links.
+    /// The default implementation reports none.
+    fn imported_count(&self) -> usize {
+        0
+    }
+
+    /// Tells the names of the services that the module holds, in the same way as [`imported_count`](#method.imported_count).
+    /// The default implementation reports none.
+    fn imported_names(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Tells how many proxies the module holds, including the ones that have been returned by the calls
+    /// to the imported services, so that the coordinator can watch them piling up over time.
+    /// The default implementation reports none.
+    fn retained_proxy_count(&self) -> usize {
+        0
+    }
+
+    /// Called when a port has been created for the link with the module of `peer_name`,
+    /// before anything is imported through it.
+    ///
+    /// It is the place to prepare what the module keeps for each peer.
+    fn port_created(&mut self, _peer_name: &str) {}
+
+    /// Called once all the ports have finished exchanging services.
+    ///
+    /// Every import has been delivered by [`import_service`](#tymethod.import_service) at this point,
+    /// so it is the right place for a setup that needs all of them.
+    /// `thread_pool` can be kept to run background work on the pool that serves the calls of the module.
+    fn bootstrap_finished(&mut self, _thread_pool: ThreadPoolHandle) {}
+
+    /// Gives a ping for each linked module, to be run by [`FoundryModule::check_links`].
+    ///
+    /// Each ping should make a cheap call to one of the services imported from the module, named by the first element.
+    /// A ping that panics, which is what a proxy does when its link is broken, tells that the link is dead.
+    /// The default implementation checks no link.
+    ///
+    /// [`FoundryModule::check_links`]: ../coordinator_interface/trait.FoundryModule.html#tymethod.check_links
+    fn ping_imports(&mut self) -> Vec<(String, Ping<'_>)> {
+        Vec::new()
+    }
+
+    /// Tells whether the module can be shut down now, asked by [`FoundryModule::shutdown`] before anything is torn down.
+    ///
+    /// It lets the module finish a critical section first, as the coordinator retries the shutdown later.
+    /// It must not be used to keep the module alive, since the coordinator may force it to shut down anyway.
+    ///
+    /// [`FoundryModule::shutdown`]: ../coordinator_interface/trait.FoundryModule.html#tymethod.shutdown
+    fn can_shutdown(&self) -> bool {
+        true
+    }
+
+    /// Called at the very beginning of the shutdown.
+    ///
+    /// All the links are still alive, so imported services can be used here for final calls, like deregistering from peers.
+    fn shutting_down(&mut self) {}
+
+    /// Gives a future that serves a named command from the host, instead of [`call`](#method.call).
+    ///
+    /// The future is spawned on the runtime of [`ModuleConfig::runtime`] without holding the module,
+    /// so other calls can be served while it awaits.
+    /// The worker that has received the call still waits for it, as RTO answers a call synchronously,
+    /// and fails with [`ModuleError::CallCancelled`] if the runtime is shut down before the future finishes.
+    /// The default implementation returns `None`, leaving every method to `call`.
+    /// It is available with the `tokio` feature, and ignored without a runtime.
+    ///
+    /// [`ModuleConfig::runtime`]: ./struct.ModuleConfig.html#structfield.runtime
+    /// [`ModuleError::CallCancelled`]: ./enum.ModuleError.html#variant.CallCancelled
+    #[cfg(feature = "tokio")]
+    fn call_async(&mut self, _method: &str, _arg: &[u8]) -> Option<CallFuture> {
+        None
+    }
+
+    /// Handles a named command from the host.
+    ///
+    /// This is the place for the commands that the module serves, while [`debug`](#tymethod.debug) is left for diagnostics.
+    /// The default implementation knows no method.
+    fn call(&mut self, method: &str, _arg: &[u8]) -> Result<Vec<u8>, ModuleError> {
+        Err(ModuleError::UnknownMethod {
+            method: method.to_owned(),
+        })
+    }
 
     /// A debug purpose method.
     ///
     /// Do whatever you want.
     /// It can be used in Mold's sandbox implementation.
+    /// A panic here is caught and reported to the caller, instead of tearing down the worker.
     fn debug(&mut self, arg: &[u8]) -> Vec<u8>;
 }
+
+/// A handle to the thread pool that serves the calls of the module, given to [`UserModule::bootstrap_finished`].
+///
+/// It only submits jobs, leaving the pool itself to the runtime.
+/// The shutdown waits for the submitted jobs to finish, so a long-running job should stop by [`UserModule::shutting_down`].
+///
+/// [`UserModule::bootstrap_finished`]: ./trait.UserModule.html#method.bootstrap_finished
+/// [`UserModule::shutting_down`]: ./trait.UserModule.html#method.shutting_down
+#[derive(Clone)]
+pub struct ThreadPoolHandle {
+    thread_pool: Arc<Mutex<ThreadPool>>,
+}
+
+impl ThreadPoolHandle {
+    pub(crate) fn new(thread_pool: Arc<Mutex<ThreadPool>>) -> Self {
+        Self {
+            thread_pool,
+        }
+    }
+
+    /// Runs `job` on a worker of the pool.
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        self.thread_pool.lock().execute(job)
+    }
+}
+
+/// A typed constructor of a module, whose argument is decoded by its codec.
+///
+/// A module implementing this can forward [`UserModule::new`] to [`new_decoded`](#method.new_decoded),
+/// instead of decoding the argument by itself.
+/// Modules that want the raw bytes just don't implement this.
+///
+/// [`UserModule::new`]: ./trait.UserModule.html#tymethod.new
+pub trait TypedInit: Sized {
+    type InitArg: DeserializeOwned;
+    /// Encoding of the argument, and of the debug calls with [`TypedDebug`]
+    ///
+    /// Use [`Cbor`] unless the host encodes them otherwise.
+    ///
+    /// [`TypedDebug`]: ./trait.TypedDebug.html
+    /// [`Cbor`]: ./struct.Cbor.html
+    type Codec: ModuleCodec;
+
+    fn new_typed(arg: Self::InitArg) -> Result<Self, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Decodes the argument with the codec and creates an instance with it.
+    ///
+    /// An argument that can't be decoded is reported as an error, like any other failure of the creation.
+    fn new_decoded(arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let arg = Self::Codec::decode(arg).map_err(|err| format!("Invalid argument: {}", err))?;
+        Self::new_typed(arg)
+    }
+
+    /// Same as [`new_decoded`](#method.new_decoded), but always decodes the argument from CBOR.
+    ///
+    /// It ignores `Codec`, so it is kept only for the modules written before the codec.
+    #[deprecated(note = "Use `new_decoded`, which decodes the argument with `Codec`")]
+    fn new_from_cbor(arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let arg = Cbor::decode(arg).map_err(|err| format!("Invalid argument: {}", err))?;
+        Self::new_typed(arg)
+    }
+}
+
+/// A typed handler of [`UserModule::debug`], whose request and response are encoded by the codec of [`TypedInit`].
+///
+/// A module implementing this can forward `debug` to [`debug_decoded`](#method.debug_decoded),
+/// and the host calls it by [`debug::call_with_codec`] with the same codec.
+///
+/// [`UserModule::debug`]: ./trait.UserModule.html#tymethod.debug
+/// [`TypedInit`]: ./trait.TypedInit.html
+/// [`debug::call_with_codec`]: ./debug/fn.call_with_codec.html
+pub trait TypedDebug: TypedInit {
+    type Request: DeserializeOwned;
+    type Response: Serialize;
+
+    fn debug_typed(&mut self, request: Self::Request) -> Self::Response;
+
+    /// Decodes the request with the codec, handles it and encodes the response.
+    ///
+    /// It panics on a request that can't be decoded, which is reported to the caller like any other panic in `debug`.
+    fn debug_decoded(&mut self, arg: &[u8]) -> Vec<u8> {
+        let request = Self::Codec::decode(arg).unwrap_or_else(|err| panic!("Invalid debug request: {}", err));
+        let response = self.debug_typed(request);
+        Self::Codec::encode(&response).unwrap_or_else(|err| panic!("Failed to encode the debug response: {}", err))
+    }
+}
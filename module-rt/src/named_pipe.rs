@@ -0,0 +1,226 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A named pipe transport for ports linked across processes on Windows.
+//!
+//! A pipe is opened for each direction, so that sending never waits for receiving.
+//! Each message is framed with its length as a big-endian `u32`, up to the same size as the TCP transport.
+
+use crate::coordinator_interface::NamedPipeEnd;
+use crate::tcp::{decode_frame_len, encode_frame_len};
+use named_pipe::{PipeClient, PipeOptions, PipeServer};
+use parking_lot::Mutex;
+use remote_trait_object::transport::{Terminate, TransportError, TransportRecv, TransportSend};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long the connecting end keeps retrying until the listening end shows up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+/// How often a receiver without a timeout checks whether it has been terminated
+const TERMINATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Establishes a connection as the given end, blocking until the other end shows up.
+pub fn connect(end: NamedPipeEnd) -> io::Result<(PipeSend, PipeRecv)> {
+    let (send, recv) = match end {
+        NamedPipeEnd::Listen(name) => {
+            let send = PipeOptions::new(pipe_path(&name, "down")).single()?.wait()?;
+            let recv = PipeOptions::new(pipe_path(&name, "up")).single()?.wait()?;
+            (Pipe::Server(send), Pipe::Server(recv))
+        }
+        NamedPipeEnd::Connect(name) => {
+            let recv = connect_client(&pipe_path(&name, "down"))?;
+            let send = connect_client(&pipe_path(&name, "up"))?;
+            (Pipe::Client(send), Pipe::Client(recv))
+        }
+    };
+
+    let terminated = Arc::new(AtomicBool::new(false));
+    Ok((
+        PipeSend {
+            pipe: Mutex::new(send),
+            terminated: Arc::clone(&terminated),
+        },
+        PipeRecv {
+            pipe: Mutex::new(recv),
+            terminated,
+        },
+    ))
+}
+
+fn pipe_path(name: &str, direction: &str) -> String {
+    format!(r"\\.\pipe\{}-{}", name, direction)
+}
+
+fn connect_client(path: &str) -> io::Result<PipeClient> {
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        match PipeClient::connect(path) {
+            Ok(client) => return Ok(client),
+            Err(err) if Instant::now() >= deadline => return Err(err),
+            Err(_) => std::thread::sleep(CONNECT_RETRY_INTERVAL),
+        }
+    }
+}
+
+enum Pipe {
+    Server(PipeServer),
+    Client(PipeClient),
+}
+
+impl Pipe {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        match self {
+            Pipe::Server(pipe) => pipe.set_read_timeout(timeout),
+            Pipe::Client(pipe) => pipe.set_read_timeout(timeout),
+        }
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        match self {
+            Pipe::Server(pipe) => pipe.set_write_timeout(timeout),
+            Pipe::Client(pipe) => pipe.set_write_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Pipe::Server(pipe) => pipe.read(buf),
+            Pipe::Client(pipe) => pipe.read(buf),
+        }
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Pipe::Server(pipe) => pipe.write(buf),
+            Pipe::Client(pipe) => pipe.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Pipe::Server(pipe) => pipe.flush(),
+            Pipe::Client(pipe) => pipe.flush(),
+        }
+    }
+}
+
+pub struct PipeSend {
+    /// Keeps frames from concurrent senders from interleaving
+    pipe: Mutex<Pipe>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl TransportSend for PipeSend {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(TransportError::Termination)
+        }
+        write_frame(&mut self.pipe.lock(), data, timeout).map_err(|err| to_transport_error(err, &self.terminated))
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+pub struct PipeRecv {
+    pipe: Mutex<Pipe>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl TransportRecv for PipeRecv {
+    fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, TransportError> {
+        read_frame(&mut self.pipe.lock(), timeout, &self.terminated)
+            .map_err(|err| to_transport_error(err, &self.terminated))
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+/// A pending read can't be interrupted, so the receiver notices the termination on its next poll.
+struct Terminator {
+    terminated: Arc<AtomicBool>,
+}
+
+impl Terminate for Terminator {
+    fn terminate(&self) {
+        self.terminated.store(true, Ordering::SeqCst);
+    }
+}
+
+fn write_frame(pipe: &mut Pipe, data: &[u8], timeout: Option<Duration>) -> io::Result<()> {
+    let len = encode_frame_len(data.len())?;
+    pipe.set_write_timeout(timeout);
+    pipe.write_all(&len)?;
+    pipe.write_all(data)
+}
+
+fn read_frame(pipe: &mut Pipe, timeout: Option<Duration>, terminated: &AtomicBool) -> io::Result<Vec<u8>> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut len = [0; 4];
+    let mut filled = 0;
+    // Polls for the beginning of a frame, keeping what has been read across the polls not to break the framing.
+    while filled < len.len() {
+        if terminated.load(Ordering::SeqCst) {
+            return Err(io::ErrorKind::ConnectionAborted.into())
+        }
+        let poll = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::ErrorKind::TimedOut.into())
+                }
+                std::cmp::min(deadline - now, TERMINATION_POLL_INTERVAL)
+            }
+            None => TERMINATION_POLL_INTERVAL,
+        };
+        pipe.set_read_timeout(Some(poll));
+        match pipe.read(&mut len[filled..]) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    // Once a frame has begun, it must be read to the end.
+    pipe.set_read_timeout(None);
+    let mut data = vec![0; decode_frame_len(len)?];
+    pipe.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn to_transport_error(err: io::Error, terminated: &AtomicBool) -> TransportError {
+    if terminated.load(Ordering::SeqCst) {
+        return TransportError::Termination
+    }
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => TransportError::TimeOut,
+        _ => TransportError::Custom,
+    }
+}
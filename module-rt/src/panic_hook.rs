@@ -0,0 +1,97 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The hooks given by [`ModuleConfig::panic_hook`], which share the one panic hook of the process.
+//!
+//! A panic is told to the hook of the module that the panicking thread works for,
+//! recognized by the id of the module in the name of the thread,
+//! while any other panic goes on to the hook that the process had before the first module installed its own.
+//!
+//! [`ModuleConfig::panic_hook`]: ../struct.ModuleConfig.html#structfield.panic_hook
+
+use crate::config::PanicHook;
+use parking_lot::{const_mutex, Mutex};
+use std::panic::{self, PanicInfo};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+
+/// Hooks of the modules alive, along with the ids to deregister them, and the ids and the names of the modules
+static HOOKS: Mutex<Vec<(u64, u64, String, PanicHook)>> = const_mutex(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static NEXT_MODULE_ID: AtomicU64 = AtomicU64::new(0);
+static INSTALL: Once = Once::new();
+
+/// Gives a module the id that tells its workers from the ones of the other modules, even of the same name.
+pub fn next_module_id() -> u64 {
+    NEXT_MODULE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Name of the threads in the pool of the module
+pub fn module_worker_name(module_name: &str, module_id: u64) -> String {
+    format!("module_worker:{}#{}", module_name, module_id)
+}
+
+/// Name of the threads in the pool that a port of the module gets for itself
+pub fn port_worker_name(module_name: &str, module_id: u64, port_name: &str) -> String {
+    format!("port_worker:{}#{}({})", module_name, module_id, port_name)
+}
+
+/// Tells `hook` the panics in the workers of the module until [`deregister`] is called with the returned id.
+pub fn register(hook: PanicHook, module_name: String, module_id: u64) -> u64 {
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !dispatch(info) {
+                previous(info)
+            }
+        }));
+    });
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    HOOKS.lock().push((id, module_id, module_name, hook));
+    id
+}
+
+pub fn deregister(id: u64) {
+    HOOKS.lock().retain(|(registered, ..)| *registered != id);
+}
+
+/// Tells the panic to the module that the thread works for, returning whether there is one.
+fn dispatch(info: &PanicInfo<'_>) -> bool {
+    let thread = std::thread::current();
+    let thread_name = match thread.name() {
+        Some(thread_name) => thread_name,
+        None => return false,
+    };
+    // The hook is called without the lock, so that a module can be registered or shut down meanwhile.
+    let hook = HOOKS
+        .lock()
+        .iter()
+        .find(|(_, module_id, module_name, _)| is_worker_of(thread_name, module_name, *module_id))
+        .map(|(_, _, module_name, hook)| (module_name.clone(), hook.clone()));
+    match hook {
+        Some((module_name, hook)) => {
+            hook.call(info, &module_name);
+            true
+        }
+        None => false,
+    }
+}
+
+fn is_worker_of(thread_name: &str, module_name: &str, module_id: u64) -> bool {
+    thread_name == module_worker_name(module_name, module_id)
+        || (thread_name.starts_with(&format!("port_worker:{}#{}(", module_name, module_id))
+            && thread_name.ends_with(')'))
+}
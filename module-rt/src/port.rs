@@ -14,79 +14,548 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::bootstrap::ExportingServicePool;
-use crate::coordinator_interface::{PartialRtoConfig, Port};
+use crate::bootstrap::{panic_message, ExportingServicePool};
+use crate::compression;
+use crate::config::RtoConfigHook;
+use crate::coordinator_interface::{
+    Compression, PartialRtoConfig, Port, PortStats, TransportKind, PORT_PROTOCOL_VERSION,
+};
+#[cfg(feature = "testing")]
+use crate::deterministic;
+use crate::error::{ImportError, ModuleError, PoolError};
+use crate::limit;
 use crate::module::UserModule;
-use fproc_sndbx::ipc::{intra::Intra, unix_socket::DomainSocket, Ipc};
-use parking_lot::Mutex;
+#[cfg(windows)]
+use crate::named_pipe;
+#[cfg(unix)]
+use crate::shared_memory;
+use crate::stats::{self, PortCounters};
+use crate::tcp;
+use crate::trace::Span;
+#[cfg(unix)]
+use fproc_sndbx::ipc::unix_socket::DomainSocket;
+use fproc_sndbx::ipc::{intra::Intra, Ipc};
+use parking_lot::{Mutex, RwLock};
 use remote_trait_object::raw_exchange::{export_service_into_handle, HandleToExchange};
+use remote_trait_object::transport::{TransportError, TransportRecv, TransportSend};
 use remote_trait_object::{Config as RtoConfig, Context as RtoContext, Service};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
 use threadpool::ThreadPool;
 
+/// Hands over the connected transport to RTO.
+type Connect = Box<dyn FnOnce(RtoConfig) -> RtoContext + Send>;
+
+/// How long a port waits for the other end's protocol version
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct ModulePort<T: UserModule> {
+    /// Name of the module that this port is linked with
+    connected_module_name: String,
     rto_context: Option<RtoContext>,
+    /// The configuration given to the initialization, for `reconnect`
+    link_config: Option<(PartialRtoConfig, TransportKind)>,
+    /// Set by `disconnect`, after which the port is removed from the module
+    disconnected: bool,
+    /// Name of the RTO config, set by the initialization
+    rto_name: Option<String>,
     user_context: Weak<Mutex<T>>,
     thread_pool: Arc<Mutex<ThreadPool>>,
-    exporting_service_pool: Arc<Mutex<ExportingServicePool>>,
+    exporting_service_pool: Arc<RwLock<ExportingServicePool>>,
+    /// Call timeout for the links whose configuration doesn't have one, given by `ModuleRuntimeConfig`
+    default_call_timeout: Option<Duration>,
+    rto_config_hook: Option<RtoConfigHook>,
+    counters: Arc<PortCounters>,
+    /// The last message that the transport has rejected for its size, until it is reported
+    oversized: limit::OversizedSlot,
+    span: Span,
 }
 
 impl<T: UserModule> ModulePort<T> {
     pub fn new(
+        connected_module_name: String,
         user_context: Weak<Mutex<T>>,
         thread_pool: Arc<Mutex<ThreadPool>>,
-        exporting_service_pool: Arc<Mutex<ExportingServicePool>>,
+        exporting_service_pool: Arc<RwLock<ExportingServicePool>>,
+        default_call_timeout: Option<Duration>,
+        rto_config_hook: Option<RtoConfigHook>,
+        span: Span,
     ) -> Self {
         Self {
+            connected_module_name,
             rto_context: None,
+            link_config: None,
+            disconnected: false,
+            rto_name: None,
             user_context,
             thread_pool,
             exporting_service_pool,
+            default_call_timeout,
+            rto_config_hook,
+            counters: Default::default(),
+            oversized: Default::default(),
+            span,
         }
     }
 
     pub fn get_rto_context(&mut self) -> &mut RtoContext {
         self.rto_context.as_mut().unwrap()
     }
+
+    pub fn thread_pool(&self) -> Arc<Mutex<ThreadPool>> {
+        Arc::clone(&self.thread_pool)
+    }
+
+    pub fn connected_module_name(&self) -> &str {
+        &self.connected_module_name
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    pub fn rto_name(&self) -> Option<&str> {
+        self.rto_name.as_deref()
+    }
+
+    /// Reports the last message that the port has failed to send for its size, only once.
+    pub fn take_oversized(&self) -> Result<(), ModuleError> {
+        match self.oversized.lock().take() {
+            Some(limit::Oversized {
+                size,
+                max,
+            }) => Err(ModuleError::MessageTooLarge {
+                name: self.connected_module_name.clone(),
+                size,
+                max,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the context of the link, which is missing before the initialization and after `disconnect`.
+    fn linked_context<E>(&self, not_initialized: E, disconnected: E) -> Result<&RtoContext, E> {
+        match &self.rto_context {
+            Some(rto_context) => Ok(rto_context),
+            None if self.disconnected => Err(disconnected),
+            None => Err(not_initialized),
+        }
+    }
+
+    /// Wraps the transport to count, limit and compress the messages, leaving the creation of the RTO context
+    /// until the config is final.
+    fn wrap_transport<S: TransportSend + 'static, R: TransportRecv + 'static>(
+        &self,
+        send: S,
+        recv: R,
+        (compression, max_message_size): (Option<Compression>, Option<usize>),
+    ) -> Connect {
+        // The compressed messages are counted, as they are what goes through the transport.
+        let (send, recv) = stats::count(send, recv, &self.counters);
+        // The other end may claim any size for a compressed message, so it is capped as a message of the transport is.
+        let (send, recv) = compression::wrap(send, recv, compression, max_message_size.unwrap_or(tcp::MAX_FRAME_SIZE));
+        // A message too large is not worth compressing.
+        let send = limit::wrap(send, max_message_size, Arc::clone(&self.oversized));
+        Box::new(move |rto_config: RtoConfig| RtoContext::new(rto_config, send, recv))
+    }
+
+    /// Establishes the transport and hands it over to RTO.
+    fn link(
+        &mut self,
+        rto_config: PartialRtoConfig,
+        ipc_arg: Vec<u8>,
+        transport: TransportKind,
+    ) -> Result<(), ModuleError> {
+        let link_config = rto_config.clone();
+        let mut rto_config = rto_config;
+        if rto_config.call_timeout.is_none() {
+            rto_config.call_timeout = self.default_call_timeout;
+        }
+        let transport_config = (rto_config.compression, rto_config.max_message_size);
+        let init_timeout = rto_config.init_timeout;
+        let mut rto_config = rto_config.into_rto_config(Arc::clone(&self.thread_pool));
+        // The ports would be indistinguishable in the logs of RTO if they all kept the default name.
+        if rto_config.name == RtoConfig::default_setup().name {
+            rto_config.name = format!("{}->{}", rto_config.name, self.connected_module_name);
+        }
+        let connect = match transport {
+            TransportKind::Intra => {
+                let (ipc_send, ipc_recv) = establish(init_timeout, move || {
+                    let (ipc_send, ipc_recv) = Intra::new(ipc_arg).split();
+                    handshake(&ipc_send, &ipc_recv)?;
+                    Ok((ipc_send, ipc_recv))
+                })?;
+                self.wrap_transport(ipc_send, ipc_recv, transport_config)
+            }
+            #[cfg(unix)]
+            TransportKind::DomainSocket => {
+                let (ipc_send, ipc_recv) = establish(init_timeout, move || {
+                    let (ipc_send, ipc_recv) = DomainSocket::new(ipc_arg).split();
+                    handshake(&ipc_send, &ipc_recv)?;
+                    Ok((ipc_send, ipc_recv))
+                })?;
+                self.wrap_transport(ipc_send, ipc_recv, transport_config)
+            }
+            #[cfg(not(unix))]
+            TransportKind::DomainSocket => {
+                return Err(ModuleError::UnsupportedTransport {
+                    transport,
+                })
+            }
+            TransportKind::Tcp => {
+                let transport_failed = |message: String| ModuleError::TransportFailed {
+                    message,
+                };
+                let end = serde_cbor::from_slice(&ipc_arg).map_err(|err| transport_failed(err.to_string()))?;
+                let (transport_send, transport_recv) = establish(init_timeout, move || {
+                    let (transport_send, transport_recv) =
+                        tcp::connect(end).map_err(|err| transport_failed(err.to_string()))?;
+                    handshake(&transport_send, &transport_recv)?;
+                    Ok((transport_send, transport_recv))
+                })?;
+                self.wrap_transport(transport_send, transport_recv, transport_config)
+            }
+            #[cfg(windows)]
+            TransportKind::NamedPipe => {
+                let transport_failed = |message: String| ModuleError::TransportFailed {
+                    message,
+                };
+                let end = serde_cbor::from_slice(&ipc_arg).map_err(|err| transport_failed(err.to_string()))?;
+                let (transport_send, transport_recv) = establish(init_timeout, move || {
+                    let (transport_send, transport_recv) =
+                        named_pipe::connect(end).map_err(|err| transport_failed(err.to_string()))?;
+                    handshake(&transport_send, &transport_recv)?;
+                    Ok((transport_send, transport_recv))
+                })?;
+                self.wrap_transport(transport_send, transport_recv, transport_config)
+            }
+            #[cfg(not(windows))]
+            TransportKind::NamedPipe => {
+                return Err(ModuleError::UnsupportedTransport {
+                    transport,
+                })
+            }
+            #[cfg(unix)]
+            TransportKind::SharedMemory => {
+                let transport_failed = |message: String| ModuleError::TransportFailed {
+                    message,
+                };
+                let end = serde_cbor::from_slice(&ipc_arg).map_err(|err| transport_failed(err.to_string()))?;
+                let (transport_send, transport_recv) = establish(init_timeout, move || {
+                    let (transport_send, transport_recv) =
+                        shared_memory::connect(end).map_err(|err| transport_failed(err.to_string()))?;
+                    handshake(&transport_send, &transport_recv)?;
+                    Ok((transport_send, transport_recv))
+                })?;
+                self.wrap_transport(transport_send, transport_recv, transport_config)
+            }
+            #[cfg(not(unix))]
+            TransportKind::SharedMemory => {
+                return Err(ModuleError::UnsupportedTransport {
+                    transport,
+                })
+            }
+            #[cfg(feature = "testing")]
+            TransportKind::Deterministic => {
+                let (transport_send, transport_recv) =
+                    deterministic::connect(&ipc_arg).map_err(|message| ModuleError::TransportFailed {
+                        message,
+                    })?;
+                // No handshake, as nothing goes through the link until the test steps it.
+                self.wrap_transport(transport_send, transport_recv, transport_config)
+            }
+            #[cfg(not(feature = "testing"))]
+            TransportKind::Deterministic => {
+                return Err(ModuleError::UnsupportedTransport {
+                    transport,
+                })
+            }
+        };
+        if let Some(hook) = &self.rto_config_hook {
+            rto_config = hook.call(rto_config);
+        }
+        self.rto_name = Some(rto_config.name.clone());
+        self.rto_context.replace(connect(rto_config));
+        self.link_config = Some((link_config, transport));
+        Ok(())
+    }
+
+    /// Tears down the link, with the GC disabled so that nothing is sent over it.
+    fn tear_down_link(&mut self) {
+        if let Some(mut rto_context) = self.rto_context.take() {
+            rto_context.disable_garbage_collection();
+            rto_context.clear_service_registry();
+        }
+    }
 }
 
 impl<T: UserModule> Service for ModulePort<T> {}
 
 impl<T: UserModule> Port for ModulePort<T> {
-    fn initialize(&mut self, rto_config: PartialRtoConfig, ipc_arg: Vec<u8>, intra: bool) {
-        assert!(self.rto_context.is_none(), "Port must be initialized only once");
-
-        let rto_config = RtoConfig {
-            name: rto_config.name,
-            call_slots: rto_config.call_slots,
-            call_timeout: rto_config.call_timeout,
-            maximum_services_num: rto_config.maximum_services_num,
-            thread_pool: Arc::clone(&self.thread_pool),
-        };
-        let rto_context = if intra {
-            let (ipc_send, ipc_recv) = Intra::new(ipc_arg).split();
-            RtoContext::new(rto_config, ipc_send, ipc_recv)
-        } else {
-            let (ipc_send, ipc_recv) = DomainSocket::new(ipc_arg).split();
-            RtoContext::new(rto_config, ipc_send, ipc_recv)
+    fn initialize(
+        &mut self,
+        rto_config: PartialRtoConfig,
+        ipc_arg: Vec<u8>,
+        transport: TransportKind,
+    ) -> Result<(), ModuleError> {
+        if self.rto_context.is_some() {
+            return Err(ModuleError::PortAlreadyInitialized {
+                name: self.connected_module_name.clone(),
+            })
+        }
+        // The module has already let go of it.
+        if self.disconnected {
+            return Err(ModuleError::PortDisconnected {
+                name: self.connected_module_name.clone(),
+            })
+        }
+        let _enter = self.span.enter();
+        event!(transport = ?transport, "initialize");
+        self.link(rto_config, ipc_arg, transport)
+    }
+
+    fn reconnect(&mut self, ipc_arg: Vec<u8>) -> Result<(), ModuleError> {
+        let _enter = self.span.enter();
+        event!("reconnect");
+        if self.disconnected {
+            return Err(ModuleError::PortDisconnected {
+                name: self.connected_module_name.clone(),
+            })
+        }
+        let (rto_config, transport) = match self.link_config.clone() {
+            Some(link_config) => link_config,
+            None => {
+                return Err(ModuleError::PortNotInitialized {
+                    name: self.connected_module_name.clone(),
+                })
+            }
         };
-        self.rto_context.replace(rto_context);
+        self.tear_down_link();
+        self.link(rto_config, ipc_arg, transport)
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.rto_context.is_some()
+    }
+
+    fn export(&mut self, ids: &[usize]) -> Result<Vec<HandleToExchange>, PoolError> {
+        let _enter = self.span.enter();
+        event!(services = ids.len(), "export");
+        let rto_context = self.linked_context(PoolError::PortNotInitialized, PoolError::Disconnected)?;
+        let pool = self.exporting_service_pool.read();
+        let handles = ids
+            .iter()
+            .map(|&id| Ok(export_service_into_handle(rto_context, pool.export(id, &self.user_context)?)))
+            .collect::<Result<Vec<_>, PoolError>>()?;
+        self.counters.add_exported(handles.len());
+        Ok(handles)
     }
 
-    fn export(&mut self, ids: &[usize]) -> Vec<HandleToExchange> {
-        let rto_context = self.rto_context.as_ref().unwrap();
-        ids.iter()
-            .map(|&id| export_service_into_handle(rto_context, self.exporting_service_pool.lock().export(id)))
-            .collect()
+    fn export_keys(&mut self, keys: &[String]) -> Result<Vec<HandleToExchange>, PoolError> {
+        let _enter = self.span.enter();
+        event!(services = keys.len(), "export");
+        let rto_context = self.linked_context(PoolError::PortNotInitialized, PoolError::Disconnected)?;
+        let pool = self.exporting_service_pool.read();
+        let handles = keys
+            .iter()
+            .map(|key| Ok(export_service_into_handle(rto_context, pool.export_by_key(key, &self.user_context)?)))
+            .collect::<Result<Vec<_>, PoolError>>()?;
+        self.counters.add_exported(handles.len());
+        Ok(handles)
     }
 
-    fn import(&mut self, slots: &[(String, HandleToExchange)]) {
+    fn export_named(&mut self, names: &[String]) -> Result<Vec<HandleToExchange>, PoolError> {
+        let _enter = self.span.enter();
+        event!(services = names.len(), "export");
+        let rto_context = self.linked_context(PoolError::PortNotInitialized, PoolError::Disconnected)?;
+        let pool = self.exporting_service_pool.read();
+        let handles = names
+            .iter()
+            .map(|name| Ok(export_service_into_handle(rto_context, pool.export_by_name(name, &self.user_context)?)))
+            .collect::<Result<Vec<_>, PoolError>>()?;
+        self.counters.add_exported(handles.len());
+        Ok(handles)
+    }
+
+    fn export_all(&mut self) -> Result<Vec<HandleToExchange>, PoolError> {
+        let ids = self.exporting_service_pool.read().indices()?;
+        self.export(&ids)
+    }
+
+    fn export_skeleton(&mut self, keys: &[String]) -> Result<Vec<HandleToExchange>, PoolError> {
+        let _enter = self.span.enter();
+        event!(services = keys.len(), "export");
+        let rto_context = self.linked_context(PoolError::PortNotInitialized, PoolError::Disconnected)?;
+        let user_context = self.user_context.upgrade().ok_or(PoolError::ModuleGone)?;
+        let mut user_context = user_context.lock();
+        let handles = keys
+            .iter()
+            .map(|key| {
+                let skeleton =
+                    user_context.take_dynamic_export(key).ok_or_else(|| PoolError::UnknownDynamicExport {
+                        key: key.clone(),
+                    })?;
+                Ok(export_service_into_handle(rto_context, skeleton))
+            })
+            .collect::<Result<Vec<_>, PoolError>>()?;
+        self.counters.add_exported(handles.len());
+        Ok(handles)
+    }
+
+    fn import(&mut self, slots: &[(String, HandleToExchange)]) -> Result<Vec<String>, ImportError> {
+        let _enter = self.span.enter();
+        event!(services = slots.len(), "import");
+        // The module drops it on shutdown, while the coordinator may still hold the port.
+        let user_context = self.user_context.upgrade().ok_or(ImportError::ModuleGone)?;
+        let rto_context = self.linked_context(ImportError::PortNotInitialized, ImportError::Disconnected)?;
+
+        // Nothing is imported unless all the slots are accepted.
+        let errors: Vec<ImportError> = {
+            let user_context = user_context.lock();
+            slots
+                .iter()
+                .filter_map(|(name, _)| user_context.validate_import(&self.connected_module_name, name).err())
+                .collect()
+        };
+        if !errors.is_empty() {
+            return Err(ImportError::Failed {
+                errors,
+            })
+        }
+
+        // A failed slot doesn't stop the rest from being tried, so that all the failures are reported at once.
+        let mut imported: Vec<String> = Vec::new();
+        let mut errors = Vec::new();
+        // The name that each handle has been first given, as the later ones are aliases of it.
+        let mut first_names: Vec<(HandleToExchange, &str)> = Vec::new();
         for (name, handle) in slots {
-            self.user_context.upgrade().unwrap().lock().import_service(
-                self.rto_context.as_ref().unwrap(),
-                name,
-                *handle,
-            )
+            let first_name = first_names.iter().find(|(first, _)| first == handle).map(|(_, first_name)| *first_name);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| match first_name {
+                Some(first_name) => {
+                    if !imported.iter().any(|imported_name| imported_name == first_name) {
+                        return Err(ImportError::Rejected {
+                            name: name.clone(),
+                            message: format!("Service has failed to be imported as `{}`", first_name),
+                        })
+                    }
+                    user_context.lock().alias_import(&self.connected_module_name, first_name, name)
+                }
+                None => user_context.lock().import_service(rto_context, &self.connected_module_name, name, *handle),
+            }))
+            .unwrap_or_else(|payload| {
+                Err(ImportError::Panicked {
+                    name: name.clone(),
+                    message: panic_message(&*payload),
+                })
+            });
+            if first_name.is_none() {
+                first_names.push((*handle, name.as_str()));
+            }
+            match result {
+                Ok(()) => imported.push(name.clone()),
+                Err(error) => errors.push(error),
+            }
         }
+        if !errors.is_empty() {
+            // Undoes the slots imported so far, latest first, so that the module is left as it was.
+            let mut user_context = user_context.lock();
+            for name in imported.iter().rev() {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    user_context.revoke_import(&self.connected_module_name, name)
+                }));
+                if let Err(payload) = result {
+                    errors.push(ImportError::Panicked {
+                        name: name.clone(),
+                        message: panic_message(&*payload),
+                    });
+                }
+            }
+            return Err(ImportError::Failed {
+                errors,
+            })
+        }
+        self.counters.add_imported(imported.len());
+        Ok(imported)
+    }
+
+    fn import_map(&mut self, map: HashMap<String, HandleToExchange>) -> Result<Vec<String>, ImportError> {
+        let mut slots: Vec<(String, HandleToExchange)> = map.into_iter().collect();
+        slots.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+        self.import(&slots)
+    }
+
+    fn disconnect(&mut self) {
+        let _enter = self.span.enter();
+        event!("disconnect");
+        self.tear_down_link();
+        self.link_config = None;
+        self.disconnected = true;
+    }
+
+    fn stats(&self) -> PortStats {
+        self.counters.snapshot()
+    }
+}
+
+/// Establishes the transport by `connect`, giving up after `timeout` if it is given.
+///
+/// The transports of the sandbox panic on a malformed argument, which is reported as a failure instead.
+/// They can't be interrupted, so the thread that runs `connect` is left behind on a timeout
+/// until the transport gives up by itself, if ever.
+fn establish<S: Send + 'static, R: Send + 'static>(
+    timeout: Option<Duration>,
+    connect: impl FnOnce() -> Result<(S, R), ModuleError> + Send + 'static,
+) -> Result<(S, R), ModuleError> {
+    let connect = move || {
+        panic::catch_unwind(AssertUnwindSafe(connect)).unwrap_or_else(|payload| {
+            Err(ModuleError::TransportFailed {
+                message: format!("Transport has panicked: {}", panic_message(&*payload)),
+            })
+        })
+    };
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return connect(),
+    };
+    let (sender, receiver) = crossbeam::channel::bounded(1);
+    thread::Builder::new()
+        .name("port_init".to_owned())
+        .spawn(move || {
+            // The port may have stopped waiting for it.
+            let _ = sender.send(connect());
+        })
+        .map_err(|err| ModuleError::TransportFailed {
+            message: err.to_string(),
+        })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(crossbeam::channel::RecvTimeoutError::Timeout) => Err(ModuleError::PortInitTimeout),
+        Err(crossbeam::channel::RecvTimeoutError::Disconnected) => unreachable!("The result is always sent"),
+    }
+}
+
+/// Exchanges the protocol versions with the other end, before the transport is handed over to RTO.
+fn handshake(send: &impl TransportSend, recv: &impl TransportRecv) -> Result<(), ModuleError> {
+    let handshake_failed = |err: TransportError| ModuleError::TransportFailed {
+        message: match err {
+            TransportError::TimeOut => "Handshake has timed out".to_owned(),
+            _ => "Handshake has failed".to_owned(),
+        },
+    };
+    send.send(&PORT_PROTOCOL_VERSION.to_be_bytes(), Some(HANDSHAKE_TIMEOUT)).map_err(handshake_failed)?;
+    let data = recv.recv(Some(HANDSHAKE_TIMEOUT)).map_err(handshake_failed)?;
+    let theirs = <[u8; 4]>::try_from(&data[..]).map(u32::from_be_bytes).map_err(|_| ModuleError::TransportFailed {
+        message: format!("Invalid handshake of {} bytes", data.len()),
+    })?;
+    if theirs != PORT_PROTOCOL_VERSION {
+        return Err(ModuleError::VersionMismatch {
+            ours: PORT_PROTOCOL_VERSION,
+            theirs,
+        })
     }
+    Ok(())
 }
@@ -0,0 +1,268 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A transport for ports linked across processes in the same host, which passes large messages through shared memory.
+//!
+//! Messages go through a domain socket, except that the ones of at least [`INLINE_LIMIT`] bytes
+//! are written to a segment mapped by both ends, leaving only their lengths to the socket.
+//! The segment has a region for each direction, which holds one message at a time
+//! until the receiver copies it out, so a message larger than the region falls back to the socket.
+//!
+//! Each message on the socket starts with a tag, telling whether the message follows or waits in the segment.
+
+use crate::coordinator_interface::SharedMemoryEnd;
+use fproc_sndbx::ipc::{unix_socket::DomainSocket, Ipc};
+use memmap2::MmapMut;
+use parking_lot::Mutex;
+use remote_trait_object::transport::{Terminate, TransportError, TransportRecv, TransportSend};
+use std::convert::TryFrom;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Size from which a message goes through the segment
+pub const INLINE_LIMIT: usize = 1 << 16;
+/// Room in front of each region for its flag, which keeps the region aligned
+const HEADER_SIZE: usize = 8;
+/// How often a sender checks whether the receiver has released the region
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+const INLINE: u8 = 0;
+const IN_SEGMENT: u8 = 1;
+
+/// Establishes a connection as the given end, blocking until the other end shows up.
+pub fn connect(
+    end: SharedMemoryEnd,
+) -> io::Result<(SharedMemorySend<impl TransportSend>, SharedMemoryRecv<impl TransportRecv>)> {
+    let (socket_send, socket_recv, segment, side) = match end {
+        SharedMemoryEnd::Create {
+            socket,
+            segment,
+            capacity,
+        } => {
+            let mapped = Segment::create(&segment, capacity);
+            // The segment is unlinked once the other end has mapped it, or on the failure, so that nothing is left behind.
+            let connected = mapped.and_then(|mapped| {
+                let (socket_send, socket_recv) = DomainSocket::new(socket).split();
+                socket_recv
+                    .recv(None)
+                    .map_err(|err| transport_io_error(err, "The other end hasn't mapped the segment"))?;
+                Ok((socket_send, socket_recv, mapped))
+            });
+            let _ = fs::remove_file(&segment);
+            let (socket_send, socket_recv, mapped) = connected?;
+            (socket_send, socket_recv, mapped, 0)
+        }
+        SharedMemoryEnd::Open {
+            socket,
+            segment,
+            capacity,
+        } => {
+            // The other end creates the segment before it waits on the socket.
+            let (socket_send, socket_recv) = DomainSocket::new(socket).split();
+            let mapped = Segment::open(&segment, capacity)?;
+            socket_send
+                .send(&[0], None)
+                .map_err(|err| transport_io_error(err, "Failed to tell the segment is mapped"))?;
+            (socket_send, socket_recv, mapped, 1)
+        }
+    };
+
+    let segment = Arc::new(segment);
+    let terminated = Arc::new(AtomicBool::new(false));
+    Ok((
+        SharedMemorySend {
+            socket: socket_send,
+            segment: Arc::clone(&segment),
+            side,
+            lock: Mutex::new(()),
+            terminated: Arc::clone(&terminated),
+        },
+        SharedMemoryRecv {
+            socket: socket_recv,
+            segment,
+            side: 1 - side,
+            terminated,
+        },
+    ))
+}
+
+fn transport_io_error(err: TransportError, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{}: {:?}", message, err))
+}
+
+/// A file mapped by both ends, which has a region of `capacity` bytes for each direction.
+struct Segment {
+    /// Kept to hold the mapping that `base` points to
+    _map: MmapMut,
+    base: *mut u8,
+    capacity: usize,
+}
+
+// The regions are only accessed behind their flags.
+unsafe impl Send for Segment {}
+unsafe impl Sync for Segment {}
+
+impl Segment {
+    fn create(path: &Path, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+        // A new file is filled with zeros, so both regions start released.
+        file.set_len(Self::len(capacity) as u64)?;
+        Self::map(&file, capacity)
+    }
+
+    fn open(path: &Path, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        if file.metadata()?.len() < Self::len(capacity) as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The segment is smaller than the capacity"))
+        }
+        Self::map(&file, capacity)
+    }
+
+    fn map(file: &fs::File, capacity: usize) -> io::Result<Self> {
+        let mut map = unsafe { MmapMut::map_mut(file)? };
+        let base = map.as_mut_ptr();
+        Ok(Self {
+            _map: map,
+            base,
+            capacity: Self::region_capacity(capacity),
+        })
+    }
+
+    /// Rounds up the capacity to keep the flag of the second region aligned.
+    fn region_capacity(capacity: usize) -> usize {
+        (capacity + HEADER_SIZE - 1) / HEADER_SIZE * HEADER_SIZE
+    }
+
+    fn len(capacity: usize) -> usize {
+        2 * (HEADER_SIZE + Self::region_capacity(capacity))
+    }
+
+    fn region(&self, side: usize) -> *mut u8 {
+        unsafe { self.base.add(side * (HEADER_SIZE + self.capacity)) }
+    }
+
+    /// Whether the region of `side` holds a message that the receiver hasn't copied out yet
+    fn occupied(&self, side: usize) -> &AtomicBool {
+        unsafe { &*(self.region(side) as *const AtomicBool) }
+    }
+
+    fn write(&self, side: usize, data: &[u8]) {
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), self.region(side).add(HEADER_SIZE), data.len()) }
+    }
+
+    fn read(&self, side: usize, len: usize) -> Vec<u8> {
+        unsafe { std::slice::from_raw_parts(self.region(side).add(HEADER_SIZE), len) }.to_vec()
+    }
+}
+
+pub struct SharedMemorySend<S> {
+    socket: S,
+    segment: Arc<Segment>,
+    /// The region that this end writes to
+    side: usize,
+    /// Held while a message occupies the region, so that the senders take turns
+    lock: Mutex<()>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl<S: TransportSend> TransportSend for SharedMemorySend<S> {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        if data.len() < INLINE_LIMIT || data.len() > self.segment.capacity {
+            let mut message = Vec::with_capacity(1 + data.len());
+            message.push(INLINE);
+            message.extend_from_slice(data);
+            return self.socket.send(&message, timeout)
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let _lock = self.lock.lock();
+        // The receiver releases the region once it has copied out the previous message.
+        while self.segment.occupied(self.side).load(Ordering::Acquire) {
+            if self.terminated.load(Ordering::SeqCst) {
+                return Err(TransportError::Termination)
+            }
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Err(TransportError::TimeOut)
+            }
+            std::thread::sleep(RELEASE_POLL_INTERVAL);
+        }
+        self.segment.write(self.side, data);
+        self.segment.occupied(self.side).store(true, Ordering::Release);
+
+        let mut message = vec![IN_SEGMENT];
+        message.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        self.socket.send(&message, remaining)
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            inner: self.socket.create_terminator(),
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+pub struct SharedMemoryRecv<R> {
+    socket: R,
+    segment: Arc<Segment>,
+    /// The region that the other end writes to
+    side: usize,
+    terminated: Arc<AtomicBool>,
+}
+
+impl<R: TransportRecv> TransportRecv for SharedMemoryRecv<R> {
+    fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, TransportError> {
+        let message = self.socket.recv(timeout)?;
+        match message.split_first() {
+            Some((&INLINE, data)) => Ok(data.to_vec()),
+            Some((&IN_SEGMENT, len)) => {
+                let len =
+                    <[u8; 8]>::try_from(len).map(u64::from_be_bytes).map_err(|_| TransportError::Custom)? as usize;
+                if len > self.segment.capacity || !self.segment.occupied(self.side).load(Ordering::Acquire) {
+                    return Err(TransportError::Custom)
+                }
+                let data = self.segment.read(self.side, len);
+                self.segment.occupied(self.side).store(false, Ordering::Release);
+                Ok(data)
+            }
+            _ => Err(TransportError::Custom),
+        }
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            inner: self.socket.create_terminator(),
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+struct Terminator {
+    inner: Box<dyn Terminate>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl Terminate for Terminator {
+    fn terminate(&self) {
+        self.terminated.store(true, Ordering::SeqCst);
+        self.inner.terminate();
+    }
+}
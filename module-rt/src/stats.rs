@@ -0,0 +1,107 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::coordinator_interface::PortStats;
+use remote_trait_object::transport::{Terminate, TransportError, TransportRecv, TransportSend};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Counters of a port, shared with its transport.
+#[derive(Default)]
+pub struct PortCounters {
+    exported: AtomicUsize,
+    imported: AtomicUsize,
+    sent_messages: AtomicUsize,
+    received_messages: AtomicUsize,
+    sent_bytes: AtomicUsize,
+    received_bytes: AtomicUsize,
+}
+
+impl PortCounters {
+    pub fn add_exported(&self, n: usize) {
+        self.exported.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_imported(&self, n: usize) {
+        self.imported.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> PortStats {
+        PortStats {
+            exported: self.exported.load(Ordering::Relaxed),
+            imported: self.imported.load(Ordering::Relaxed),
+            sent_messages: self.sent_messages.load(Ordering::Relaxed),
+            received_messages: self.received_messages.load(Ordering::Relaxed),
+            sent_bytes: self.sent_bytes.load(Ordering::Relaxed),
+            received_bytes: self.received_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Wraps both ends of a transport to count the messages going through them.
+pub fn count<S: TransportSend, R: TransportRecv>(
+    send: S,
+    recv: R,
+    counters: &Arc<PortCounters>,
+) -> (CountingSend<S>, CountingRecv<R>) {
+    (
+        CountingSend {
+            inner: send,
+            counters: Arc::clone(counters),
+        },
+        CountingRecv {
+            inner: recv,
+            counters: Arc::clone(counters),
+        },
+    )
+}
+
+pub struct CountingSend<S> {
+    inner: S,
+    counters: Arc<PortCounters>,
+}
+
+impl<S: TransportSend> TransportSend for CountingSend<S> {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        self.inner.send(data, timeout)?;
+        self.counters.sent_messages.fetch_add(1, Ordering::Relaxed);
+        self.counters.sent_bytes.fetch_add(data.len(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        self.inner.create_terminator()
+    }
+}
+
+pub struct CountingRecv<R> {
+    inner: R,
+    counters: Arc<PortCounters>,
+}
+
+impl<R: TransportRecv> TransportRecv for CountingRecv<R> {
+    fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, TransportError> {
+        let data = self.inner.recv(timeout)?;
+        self.counters.received_messages.fetch_add(1, Ordering::Relaxed);
+        self.counters.received_bytes.fetch_add(data.len(), Ordering::Relaxed);
+        Ok(data)
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        self.inner.create_terminator()
+    }
+}
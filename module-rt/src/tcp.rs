@@ -0,0 +1,170 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A TCP transport for ports linked across hosts.
+//!
+//! Each message is framed with its length as a big-endian `u32`, up to [`MAX_FRAME_SIZE`].
+
+use crate::coordinator_interface::TcpEnd;
+use parking_lot::Mutex;
+use remote_trait_object::transport::{Terminate, TransportError, TransportRecv, TransportSend};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long the connecting end keeps retrying until the listening end shows up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+/// The largest message either end sends or accepts,
+/// so that a corrupted length can't make the receiver allocate without bound.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Establishes a connection as the given end, blocking until the other end shows up.
+pub fn connect(end: TcpEnd) -> io::Result<(TcpSend, TcpRecv)> {
+    let stream = match end {
+        TcpEnd::Listen(addr) => TcpListener::bind(addr)?.accept()?.0,
+        TcpEnd::Connect(addr) => {
+            let deadline = Instant::now() + CONNECT_TIMEOUT;
+            loop {
+                match TcpStream::connect(addr) {
+                    Ok(stream) => break stream,
+                    Err(err) if Instant::now() >= deadline => return Err(err),
+                    Err(_) => std::thread::sleep(CONNECT_RETRY_INTERVAL),
+                }
+            }
+        }
+    };
+    stream.set_nodelay(true)?;
+
+    let stream = Arc::new(stream);
+    let terminated = Arc::new(AtomicBool::new(false));
+    Ok((
+        TcpSend {
+            stream: Arc::clone(&stream),
+            write_lock: Mutex::new(()),
+            terminated: Arc::clone(&terminated),
+        },
+        TcpRecv {
+            stream,
+            terminated,
+        },
+    ))
+}
+
+pub struct TcpSend {
+    stream: Arc<TcpStream>,
+    /// Keeps frames from concurrent senders from interleaving
+    write_lock: Mutex<()>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl TransportSend for TcpSend {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        let _guard = self.write_lock.lock();
+        write_frame(&self.stream, data, timeout).map_err(|err| to_transport_error(err, &self.terminated))
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            stream: Arc::clone(&self.stream),
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+pub struct TcpRecv {
+    stream: Arc<TcpStream>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl TransportRecv for TcpRecv {
+    fn recv(&self, timeout: Option<Duration>) -> Result<Vec<u8>, TransportError> {
+        read_frame(&self.stream, timeout).map_err(|err| to_transport_error(err, &self.terminated))
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        Box::new(Terminator {
+            stream: Arc::clone(&self.stream),
+            terminated: Arc::clone(&self.terminated),
+        })
+    }
+}
+
+struct Terminator {
+    stream: Arc<TcpStream>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl Terminate for Terminator {
+    fn terminate(&self) {
+        self.terminated.store(true, Ordering::SeqCst);
+        // The other end might have closed the connection already.
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn write_frame(mut stream: &TcpStream, data: &[u8], timeout: Option<Duration>) -> io::Result<()> {
+    let len = encode_frame_len(data.len())?;
+    stream.set_write_timeout(timeout)?;
+    stream.write_all(&len)?;
+    stream.write_all(data)
+}
+
+fn read_frame(mut stream: &TcpStream, timeout: Option<Duration>) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    stream.set_read_timeout(timeout)?;
+    stream.read_exact(&mut len)?;
+    // Once a frame has begun, it must be read to the end not to break the framing.
+    stream.set_read_timeout(None)?;
+    let mut data = vec![0; decode_frame_len(len)?];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Rejects a message too large to be framed, before anything of it is written.
+pub fn encode_frame_len(len: usize) -> io::Result<[u8; 4]> {
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Frame of {} bytes exceeds {} bytes", len, MAX_FRAME_SIZE),
+        ))
+    }
+    Ok((len as u32).to_be_bytes())
+}
+
+/// Rejects a length beyond [`MAX_FRAME_SIZE`], after which the framing can't be trusted anymore.
+pub fn decode_frame_len(len: [u8; 4]) -> io::Result<usize> {
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes exceeds {} bytes", len, MAX_FRAME_SIZE),
+        ))
+    }
+    Ok(len)
+}
+
+fn to_transport_error(err: io::Error, terminated: &AtomicBool) -> TransportError {
+    if terminated.load(Ordering::SeqCst) {
+        return TransportError::Termination
+    }
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => TransportError::TimeOut,
+        _ => TransportError::Custom,
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A harness to run and link modules in the same process, for tests.
+//!
+//! It is available with the `testing` feature.
+
+use crate::coordinator_interface::{FoundryModule, PartialRtoConfig, Port, PortStats, TransportKind};
+use crate::deterministic;
+use crate::error::{ImportError, ModuleError, PoolError};
+use crate::link::link_all;
+use crate::module::UserModule;
+use fproc_sndbx::execution::executor::{add_function_pool, execute, Context as ExecutorContext, PlainThread};
+use fproc_sndbx::ipc::{generate_random_name, intra::Intra, Ipc};
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use remote_trait_object::raw_exchange::HandleToExchange;
+use remote_trait_object::{Config as RtoConfig, Context as RtoContext, Service, ServiceToImport};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub use crate::deterministic::DeterministicLink;
+
+type LinkExports = dyn Fn(usize, usize) -> Vec<(usize, String)>;
+
+struct Entry {
+    /// Name of the function pool that runs the module
+    pool_name: String,
+    init: Vec<u8>,
+    exports: Vec<(String, Vec<u8>)>,
+}
+
+/// A builder of modules that are run in threads and linked with each other over [`Intra`].
+///
+/// Modules are named after their indices in the order they are added.
+pub struct TestHarness {
+    entries: Vec<Entry>,
+    /// `None` to export all the services for every link
+    link_exports: Option<Box<LinkExports>>,
+}
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestHarness {
+    /// Creates a harness where each module exports all of its services to every other module,
+    /// which imports them with their indices as the names.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            link_exports: None,
+        }
+    }
+
+    /// Adds a module that will be initialized with `init` and `exports`.
+    pub fn add_module<M: UserModule + 'static>(mut self, init: &[u8], exports: &[(String, Vec<u8>)]) -> Self {
+        let pool_name = generate_random_name();
+        add_function_pool(pool_name.clone(), Arc::new(execute_module::<M>));
+        self.entries.push(Entry {
+            pool_name,
+            init: init.to_vec(),
+            exports: exports.to_vec(),
+        });
+        self
+    }
+
+    /// Sets what to export for each link, as [`link_all`] does.
+    ///
+    /// [`link_all`]: ../fn.link_all.html
+    pub fn link_exports(mut self, link_exports: impl Fn(usize, usize) -> Vec<(usize, String)> + 'static) -> Self {
+        self.link_exports = Some(Box::new(link_exports));
+        self
+    }
+
+    /// Runs, links and bootstraps all the modules.
+    ///
+    /// It panics if any of the steps fails.
+    pub fn build(self) -> Vec<TestModule> {
+        let modules: Vec<TestModule> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let module = TestModule::run(&entry.pool_name);
+                module.module.write().initialize(&entry.init, &entry.exports).unwrap();
+                module
+            })
+            .collect();
+
+        let names: Vec<String> = (0..modules.len()).map(|i| i.to_string()).collect();
+        let mut guards: Vec<_> = modules.iter().map(|module| module.module.write()).collect();
+        let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+            .iter()
+            .map(String::as_str)
+            .zip(guards.iter_mut().map(|guard| &mut **guard as &mut dyn FoundryModule))
+            .collect();
+        let export_nums: Vec<usize> = self.entries.iter().map(|entry| entry.exports.len()).collect();
+        let link_exports = self.link_exports.unwrap_or_else(|| {
+            Box::new(move |i: usize, _: usize| (0..export_nums[i]).map(|index| (index, index.to_string())).collect())
+        });
+        link_all(&mut ends, &*link_exports, TransportKind::Intra).unwrap();
+        for guard in guards.iter_mut() {
+            guard.finish_bootstrap().unwrap();
+        }
+        drop(guards);
+        modules
+    }
+}
+
+/// A module run by [`TestHarness`].
+pub struct TestModule {
+    pub module: Arc<RwLock<dyn FoundryModule>>,
+    rto_context: RtoContext,
+    _executor: ExecutorContext<Intra, PlainThread>,
+}
+
+impl TestModule {
+    fn run(pool_name: &str) -> Self {
+        let mut executor = execute::<Intra, PlainThread>(pool_name).unwrap();
+        let (transport_send, transport_recv) = executor.ipc.take().unwrap().split();
+        let (rto_context, module): (_, ServiceToImport<dyn FoundryModule>) =
+            RtoContext::with_initial_service_import(RtoConfig::default_setup(), transport_send, transport_recv);
+        Self {
+            module: module.into_proxy(),
+            rto_context,
+            _executor: executor,
+        }
+    }
+
+    /// Shuts down the module.
+    pub fn shutdown(self) {
+        self.module.write().shutdown().unwrap();
+        self.rto_context.disable_garbage_collection();
+    }
+}
+
+/// A port without a link, which hands the imports over to a user module that it owns.
+///
+/// It lets a test drive [`UserModule::import_service`] with the handles that it makes up,
+/// without running the module or linking it with another one.
+/// Exports give fake handles numbered from zero, and the imported proxies are bound to a link that goes nowhere,
+/// so they must not be called.
+///
+/// [`UserModule::import_service`]: ../trait.UserModule.html#tymethod.import_service
+pub struct MockPort<T: UserModule> {
+    /// Dropped before `rto_context`, whose GC is disabled by then
+    module: Mutex<T>,
+    rto_context: RtoContext,
+    /// Name of the module that the imports are made from
+    exporter_module: String,
+    /// Every slot passed to `import`, in order
+    slots: Vec<(String, HandleToExchange)>,
+    next_handle: u64,
+    stats: PortStats,
+    disconnected: bool,
+}
+
+impl<T: UserModule> MockPort<T> {
+    pub fn new(module: T, exporter_module: &str) -> Self {
+        let link = DeterministicLink::new();
+        let (ipc_arg, _) = link.arguments_for_both_ends();
+        let (transport_send, transport_recv) = deterministic::connect(&ipc_arg).unwrap();
+        Self {
+            module: Mutex::new(module),
+            rto_context: RtoContext::new(RtoConfig::default_setup(), transport_send, transport_recv),
+            exporter_module: exporter_module.to_owned(),
+            slots: Vec::new(),
+            next_handle: 0,
+            stats: PortStats::default(),
+            disconnected: false,
+        }
+    }
+
+    pub fn module(&self) -> MutexGuard<'_, T> {
+        self.module.lock()
+    }
+
+    /// Returns every slot that has been passed to `import`, including the failed ones.
+    pub fn slots(&self) -> &[(String, HandleToExchange)] {
+        &self.slots
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    fn fake_handles(&mut self, n: usize) -> Result<Vec<HandleToExchange>, PoolError> {
+        if self.disconnected {
+            return Err(PoolError::Disconnected)
+        }
+        let handles = (self.next_handle..self.next_handle + n as u64).map(fake_handle).collect();
+        self.next_handle += n as u64;
+        self.stats.exported += n;
+        Ok(handles)
+    }
+
+    fn check_linked(&self) -> Result<(), ModuleError> {
+        if self.disconnected {
+            return Err(ModuleError::PortDisconnected {
+                name: self.exporter_module.clone(),
+            })
+        }
+        Ok(())
+    }
+}
+
+impl<T: UserModule> Drop for MockPort<T> {
+    fn drop(&mut self) {
+        self.rto_context.disable_garbage_collection();
+    }
+}
+
+impl<T: UserModule> Service for MockPort<T> {}
+
+impl<T: UserModule> Port for MockPort<T> {
+    fn initialize(
+        &mut self,
+        _rto_config: PartialRtoConfig,
+        _ipc_arg: Vec<u8>,
+        _transport: TransportKind,
+    ) -> Result<(), ModuleError> {
+        self.check_linked()
+    }
+
+    fn reconnect(&mut self, _ipc_arg: Vec<u8>) -> Result<(), ModuleError> {
+        self.check_linked()
+    }
+
+    fn is_initialized(&self) -> bool {
+        // It is linked with the module from its creation.
+        !self.disconnected
+    }
+
+    fn export(&mut self, ids: &[usize]) -> Result<Vec<HandleToExchange>, PoolError> {
+        self.fake_handles(ids.len())
+    }
+
+    fn export_keys(&mut self, keys: &[String]) -> Result<Vec<HandleToExchange>, PoolError> {
+        self.fake_handles(keys.len())
+    }
+
+    fn export_named(&mut self, names: &[String]) -> Result<Vec<HandleToExchange>, PoolError> {
+        self.fake_handles(names.len())
+    }
+
+    fn export_all(&mut self) -> Result<Vec<HandleToExchange>, PoolError> {
+        // It has no services of its own to count.
+        Ok(Vec::new())
+    }
+
+    fn export_skeleton(&mut self, keys: &[String]) -> Result<Vec<HandleToExchange>, PoolError> {
+        self.fake_handles(keys.len())
+    }
+
+    fn import(&mut self, slots: &[(String, HandleToExchange)]) -> Result<Vec<String>, ImportError> {
+        if self.disconnected {
+            return Err(ImportError::Disconnected)
+        }
+        self.slots.extend_from_slice(slots);
+        let mut module = self.module.lock();
+        // Same as a real port, nothing is imported unless all the slots are accepted.
+        let errors: Vec<ImportError> =
+            slots.iter().filter_map(|(name, _)| module.validate_import(&self.exporter_module, name).err()).collect();
+        if !errors.is_empty() {
+            return Err(ImportError::Failed {
+                errors,
+            })
+        }
+        let mut imported = Vec::new();
+        let mut errors = Vec::new();
+        for (name, handle) in slots {
+            match module.import_service(&self.rto_context, &self.exporter_module, name, *handle) {
+                Ok(()) => imported.push(name.clone()),
+                Err(error) => errors.push(error),
+            }
+        }
+        if !errors.is_empty() {
+            for name in imported.iter().rev() {
+                module.revoke_import(&self.exporter_module, name);
+            }
+            return Err(ImportError::Failed {
+                errors,
+            })
+        }
+        self.stats.imported += imported.len();
+        Ok(imported)
+    }
+
+    fn import_map(&mut self, map: HashMap<String, HandleToExchange>) -> Result<Vec<String>, ImportError> {
+        let mut slots: Vec<(String, HandleToExchange)> = map.into_iter().collect();
+        slots.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+        self.import(&slots)
+    }
+
+    fn stats(&self) -> PortStats {
+        self.stats.clone()
+    }
+
+    fn disconnect(&mut self) {
+        self.disconnected = true;
+    }
+}
+
+/// Makes a handle out of a number, which RTO doesn't offer otherwise.
+fn fake_handle(id: u64) -> HandleToExchange {
+    serde_cbor::from_slice(&serde_cbor::to_vec(&id).unwrap()).unwrap()
+}
+
+fn execute_module<M: UserModule + 'static>(args: Vec<String>) {
+    crate::start::<Intra, M>(args);
+}
@@ -0,0 +1,74 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::CallTimeoutError;
+use crossbeam::channel;
+use parking_lot::{const_mutex, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use threadpool::ThreadPool;
+
+/// Number of the threads making the calls of [`call_with_timeout`], which is also the most calls outstanding at once
+pub const MAX_OUTSTANDING_CALLS: usize = 16;
+
+/// Created on the first call
+static CALLERS: Mutex<Option<ThreadPool>> = const_mutex(None);
+/// Calls running in `CALLERS`, including the abandoned ones
+static OUTSTANDING_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Makes a call to an imported service, giving up on it if it doesn't return within `timeout`.
+///
+/// RTO fixes the timeout of a link when the port is initialized, so this is for a method slower or faster than the others.
+/// The call runs in a worker of a pool shared by the process, which is left behind, not cancelled, on the timeout;
+/// it keeps the worker and a call slot of the port until the answer comes or the `call_timeout` of the port expires.
+/// So `timeout` can only be shorter than the one of the port, after which the proxy panics as usual.
+/// The proxy has to be moved into `call`, which an `Arc` of it makes easy.
+///
+/// Fails with [`CallTimeoutError::TimedOut`] on the timeout, or with [`CallTimeoutError::TooManyOutstanding`]
+/// without making the call if [`MAX_OUTSTANDING_CALLS`] are still running,
+/// and resumes the panic if the call has panicked.
+///
+/// [`CallTimeoutError::TimedOut`]: ./enum.CallTimeoutError.html#variant.TimedOut
+/// [`CallTimeoutError::TooManyOutstanding`]: ./enum.CallTimeoutError.html#variant.TooManyOutstanding
+/// [`MAX_OUTSTANDING_CALLS`]: ./constant.MAX_OUTSTANDING_CALLS.html
+pub fn call_with_timeout<R: Send + 'static>(
+    call: impl FnOnce() -> R + Send + 'static,
+    timeout: Duration,
+) -> Result<R, CallTimeoutError> {
+    // Every call gets a worker of its own, so that none spends its timeout waiting in the queue.
+    if OUTSTANDING_CALLS.fetch_add(1, Ordering::SeqCst) >= MAX_OUTSTANDING_CALLS {
+        OUTSTANDING_CALLS.fetch_sub(1, Ordering::SeqCst);
+        return Err(CallTimeoutError::TooManyOutstanding {
+            limit: MAX_OUTSTANDING_CALLS,
+        })
+    }
+    let (result_send, result_recv) = channel::bounded(1);
+    CALLERS
+        .lock()
+        .get_or_insert_with(|| ThreadPool::with_name("call_with_timeout".to_owned(), MAX_OUTSTANDING_CALLS))
+        .execute(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(call));
+            OUTSTANDING_CALLS.fetch_sub(1, Ordering::SeqCst);
+            // Nobody waits for the result after the timeout.
+            let _ = result_send.send(result);
+        });
+    match result_recv.recv_timeout(timeout) {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(payload)) => panic::resume_unwind(payload),
+        Err(_) => Err(CallTimeoutError::TimedOut),
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "tokio")]
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::coordinator_interface::FoundryModule;
+use fmoudle_rt::{CallFuture, ImportError, ModuleConfig, ModuleError, UserModule};
+use remote_trait_object::raw_exchange::{HandleToExchange, Skeleton};
+use remote_trait_object::Context as RtoContext;
+use std::time::{Duration, Instant};
+
+/// Sleeps on a timer for the milliseconds given to `sleep`.
+struct Sleeper;
+
+impl UserModule for Sleeper {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("Unknown service {}", ctor_name).into())
+    }
+
+    fn import_service(
+        &mut self,
+        _rto_context: &RtoContext,
+        _exporter_module: &str,
+        name: &str,
+        _handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        Err(ImportError::Rejected {
+            name: name.to_owned(),
+            message: "Nothing to import".to_owned(),
+        })
+    }
+
+    fn call_async(&mut self, method: &str, arg: &[u8]) -> Option<CallFuture> {
+        if method == "explode" {
+            return Some(Box::pin(explode()))
+        }
+        if method != "sleep" {
+            return None
+        }
+        let millis: u64 = serde_cbor::from_slice(arg).unwrap();
+        Some(Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            Ok(serde_cbor::to_vec(&millis).unwrap())
+        }))
+    }
+
+    fn debug(&mut self, _arg: &[u8]) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+async fn explode() -> Result<Vec<u8>, ModuleError> {
+    panic!("Exploded on the runtime")
+}
+
+#[test]
+fn call_async() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_time().build().unwrap();
+    let mut module = fmoudle_rt::create_foundry_module_with_config(Sleeper, &[], ModuleConfig {
+        runtime: Some(runtime.handle().clone()),
+        ..ModuleConfig::default()
+    });
+    module.finish_bootstrap().unwrap();
+    let foundry_module = &module;
+
+    // The module is not held while the calls await, so they sleep together.
+    let start = Instant::now();
+    crossbeam::scope(|scope| {
+        let calls: Vec<_> = (0..2)
+            .map(|_| scope.spawn(|_| foundry_module.call("sleep", &serde_cbor::to_vec(&300u64).unwrap()).unwrap()))
+            .collect();
+        for call in calls {
+            assert_eq!(serde_cbor::from_slice::<u64>(&call.join().unwrap()).unwrap(), 300);
+        }
+    })
+    .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(start.elapsed() < Duration::from_millis(550));
+
+    // The other methods are left to `call`.
+    assert_eq!(
+        module.call("wake", &[]),
+        Err(ModuleError::UnknownMethod {
+            method: "wake".to_owned(),
+        })
+    );
+    // A panic on the runtime is caught there and brought back to the caller.
+    assert_eq!(
+        module.call("explode", &[]),
+        Err(ModuleError::Panicked {
+            message: "Exploded on the runtime".to_owned(),
+        })
+    );
+
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn call_async_after_runtime_shutdown() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_time().build().unwrap();
+    let mut module = fmoudle_rt::create_foundry_module_with_config(Sleeper, &[], ModuleConfig {
+        runtime: Some(runtime.handle().clone()),
+        ..ModuleConfig::default()
+    });
+    module.finish_bootstrap().unwrap();
+    runtime.shutdown_background();
+
+    assert_eq!(module.call("sleep", &serde_cbor::to_vec(&10u64).unwrap()), Err(ModuleError::CallCancelled));
+
+    module.shutdown().unwrap();
+}
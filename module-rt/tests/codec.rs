@@ -0,0 +1,115 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::{DebugError, ImportError, ModuleCodec, ModuleError, TypedDebug, TypedInit, UserModule};
+use remote_trait_object::raw_exchange::{HandleToExchange, Skeleton};
+use remote_trait_object::Context as RtoContext;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+struct Bincode;
+
+impl ModuleCodec for Bincode {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
+
+/// Adds its offset to the sum of the numbers of the request on `debug`.
+struct Adder {
+    offset: i64,
+}
+
+impl TypedInit for Adder {
+    type InitArg = i64;
+    type Codec = Bincode;
+
+    fn new_typed(offset: Self::InitArg) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
+            offset,
+        })
+    }
+}
+
+impl TypedDebug for Adder {
+    type Request = Vec<i64>;
+    type Response = i64;
+
+    fn debug_typed(&mut self, numbers: Self::Request) -> Self::Response {
+        self.offset + numbers.iter().sum::<i64>()
+    }
+}
+
+impl UserModule for Adder {
+    fn new(arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::new_decoded(arg)
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("Unknown constructor `{}`", ctor_name).into())
+    }
+
+    fn import_service(
+        &mut self,
+        _rto_context: &RtoContext,
+        _exporter_module: &str,
+        name: &str,
+        _handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        Err(ImportError::Rejected {
+            name: name.to_owned(),
+            message: "Nothing to import".to_owned(),
+        })
+    }
+
+    fn debug(&mut self, arg: &[u8]) -> Vec<u8> {
+        self.debug_decoded(arg)
+    }
+}
+
+#[test]
+fn bincode_codec() {
+    // CBOR is not what the module takes.
+    assert!(Adder::new(&serde_cbor::to_vec(&"ten").unwrap()).is_err());
+
+    let adder = Adder::new(&bincode::serialize(&10i64).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(adder, &[]);
+
+    let sum: i64 = fmoudle_rt::debug::call_with_codec::<Bincode, _, _>(&module, &vec![1i64, 2, 3]).unwrap();
+    assert_eq!(sum, 16);
+
+    // The module panics on a request in another codec.
+    match fmoudle_rt::debug::call_typed::<_, i64>(&module, &vec![1i64, 2, 3]) {
+        Err(DebugError::Module {
+            error: ModuleError::Panicked {
+                ..
+            },
+        }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    module.shutdown().unwrap();
+}
@@ -0,0 +1,85 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::coordinator_interface::PartialRtoConfig;
+use fmoudle_rt::ConfigError;
+use parking_lot::Mutex;
+use remote_trait_object::Config as RtoConfig;
+use std::sync::Arc;
+use std::time::Duration;
+use threadpool::ThreadPool;
+
+#[test]
+fn partial_rto_config_keeps_maximum_services_num() {
+    let mut config = RtoConfig::default_setup();
+    config.maximum_services_num = 3;
+
+    let partial = PartialRtoConfig::from_rto_config(config);
+    assert_eq!(partial.maximum_services_num, 3);
+
+    let partial: PartialRtoConfig = serde_cbor::from_slice(&serde_cbor::to_vec(&partial).unwrap()).unwrap();
+    assert_eq!(partial.maximum_services_num, 3);
+}
+
+#[test]
+fn partial_rto_config_builder() {
+    let default = PartialRtoConfig::from_rto_config(RtoConfig::default_setup());
+    let config = PartialRtoConfig::builder().build().unwrap();
+    assert_eq!(config.name, default.name);
+    assert_eq!(config.call_slots, default.call_slots);
+    assert_eq!(config.call_timeout, default.call_timeout);
+    assert_eq!(config.maximum_services_num, default.maximum_services_num);
+
+    let config = PartialRtoConfig::builder()
+        .name("module1")
+        .call_slots(4)
+        .call_timeout(Some(Duration::from_millis(10)))
+        .maximum_services_num(3)
+        .build()
+        .unwrap();
+    assert_eq!(config.name, "module1");
+    assert_eq!(config.call_slots, 4);
+    assert_eq!(config.call_timeout, Some(Duration::from_millis(10)));
+    assert_eq!(config.maximum_services_num, 3);
+}
+
+#[test]
+fn partial_rto_config_without_call_slots() {
+    assert_eq!(PartialRtoConfig::builder().call_slots(0).build().unwrap_err(), ConfigError::ZeroCallSlots);
+}
+
+#[test]
+fn partial_rto_config_into_rto_config() {
+    let thread_pool = Arc::new(Mutex::new(ThreadPool::new(1)));
+    let config = RtoConfig {
+        name: "module1".to_owned(),
+        call_slots: 7,
+        call_timeout: Some(Duration::from_millis(30)),
+        maximum_services_num: 11,
+        thread_pool: Arc::clone(&thread_pool),
+    };
+
+    let partial = PartialRtoConfig::from_rto_config(config);
+    let partial: PartialRtoConfig = serde_cbor::from_slice(&serde_cbor::to_vec(&partial).unwrap()).unwrap();
+    let config = partial.into_rto_config(Arc::clone(&thread_pool));
+    assert_eq!(config.name, "module1");
+    assert_eq!(config.call_slots, 7);
+    assert_eq!(config.call_timeout, Some(Duration::from_millis(30)));
+    assert_eq!(config.maximum_services_num, 11);
+    assert!(Arc::ptr_eq(&config.thread_pool, &thread_pool));
+}
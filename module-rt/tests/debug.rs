@@ -0,0 +1,103 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::{DebugError, ImportError, ModuleError, UserModule};
+use remote_trait_object::raw_exchange::{HandleToExchange, Skeleton};
+use remote_trait_object::Context as RtoContext;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Request {
+    numbers: Vec<i32>,
+    label: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Response {
+    sum: i32,
+    label: String,
+}
+
+/// Sums up the numbers of the request on `debug`.
+struct Adder;
+
+impl UserModule for Adder {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("Unknown constructor `{}`", ctor_name).into())
+    }
+
+    fn import_service(
+        &mut self,
+        _rto_context: &RtoContext,
+        _exporter_module: &str,
+        name: &str,
+        _handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        Err(ImportError::Rejected {
+            name: name.to_owned(),
+            message: "Nothing to import".to_owned(),
+        })
+    }
+
+    fn debug(&mut self, arg: &[u8]) -> Vec<u8> {
+        let request: Request = serde_cbor::from_slice(arg).unwrap();
+        serde_cbor::to_vec(&Response {
+            sum: request.numbers.iter().sum(),
+            label: request.label,
+        })
+        .unwrap()
+    }
+}
+
+#[test]
+fn call_typed() {
+    let mut module = fmoudle_rt::create_foundry_module(Adder, &[]);
+
+    let response: Response = fmoudle_rt::debug::call_typed(&module, &Request {
+        numbers: vec![1, 2, 3],
+        label: "sum".to_owned(),
+    })
+    .unwrap();
+    assert_eq!(response, Response {
+        sum: 6,
+        label: "sum".to_owned(),
+    });
+
+    // The module panics on a request that it doesn't know.
+    match fmoudle_rt::debug::call_typed::<_, Response>(&module, &"unknown") {
+        Err(DebugError::Module {
+            error: ModuleError::Panicked {
+                ..
+            },
+        }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    module.shutdown().unwrap();
+    assert_eq!(module.debug(&[]), Err(ModuleError::ShutDown));
+    assert_eq!(module.call("sum", &[]), Err(ModuleError::ShutDown));
+    assert_eq!(module.check_links(), Err(ModuleError::ShutDown));
+}
@@ -0,0 +1,132 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::{ConfigError, DebugError, ImportError, LinkError, ModuleError, ModuleRtError, PoolError};
+use std::collections::HashSet;
+use std::error::Error;
+
+#[test]
+fn module_rt_error_display() {
+    let errors = vec![
+        ModuleRtError::AlreadyInitialized,
+        ModuleRtError::DuplicatePort {
+            name: "peer".to_owned(),
+        },
+        ModuleRtError::PortNotInitialized {
+            name: Some("peer".to_owned()),
+        },
+        ModuleRtError::PortNotInitialized {
+            name: None,
+        },
+        ModuleRtError::ExportIndexOutOfRange {
+            index: 3,
+            len: 2,
+        },
+        ModuleRtError::ImportFailed {
+            error: ImportError::Disconnected,
+        },
+        ModuleRtError::ModuleGone,
+        ModuleRtError::Panicked {
+            message: "oops".to_owned(),
+        },
+        ModuleRtError::VersionMismatch {
+            ours: 1,
+            theirs: 2,
+        },
+        ModuleRtError::Module {
+            error: ModuleError::NotShutDown,
+        },
+        ModuleRtError::Export {
+            error: PoolError::AlreadyCleared,
+        },
+        ModuleRtError::Link {
+            error: LinkError::Topology {
+                module: String::new(),
+                message: "empty".to_owned(),
+            },
+        },
+        ModuleRtError::Config {
+            error: ConfigError::ZeroCallSlots,
+        },
+        ModuleRtError::Codec {
+            message: "oops".to_owned(),
+        },
+    ];
+    let messages: HashSet<String> = errors.iter().map(ToString::to_string).collect();
+    assert!(messages.iter().all(|message| !message.is_empty()));
+    assert_eq!(messages.len(), errors.len());
+}
+
+#[test]
+fn module_rt_error_from() {
+    // The same failure is the same variant, whichever API has returned it.
+    assert_eq!(ModuleRtError::from(ModuleError::ShutDown), ModuleRtError::ModuleGone);
+    assert_eq!(ModuleRtError::from(PoolError::ModuleGone), ModuleRtError::ModuleGone);
+    assert_eq!(ModuleRtError::from(ImportError::ModuleGone), ModuleRtError::ModuleGone);
+    assert_eq!(
+        ModuleRtError::from(ModuleError::PortNotInitialized {
+            name: "peer".to_owned(),
+        }),
+        ModuleRtError::PortNotInitialized {
+            name: Some("peer".to_owned()),
+        }
+    );
+    assert_eq!(ModuleRtError::from(PoolError::PortNotInitialized), ModuleRtError::PortNotInitialized {
+        name: None,
+    });
+    assert_eq!(ModuleRtError::from(ModuleError::AlreadyInitialized), ModuleRtError::AlreadyInitialized);
+    assert_eq!(
+        ModuleRtError::from(PoolError::IndexOutOfRange {
+            index: 3,
+            len: 2,
+        }),
+        ModuleRtError::ExportIndexOutOfRange {
+            index: 3,
+            len: 2,
+        }
+    );
+    assert_eq!(ModuleRtError::from(ImportError::Disconnected), ModuleRtError::ImportFailed {
+        error: ImportError::Disconnected,
+    });
+    assert_eq!(
+        ModuleRtError::from(DebugError::Module {
+            error: ModuleError::Panicked {
+                message: "oops".to_owned(),
+            },
+        }),
+        ModuleRtError::Panicked {
+            message: "oops".to_owned(),
+        }
+    );
+    // The rest keep the error of the API.
+    assert_eq!(ModuleRtError::from(ModuleError::NotShutDown), ModuleRtError::Module {
+        error: ModuleError::NotShutDown,
+    });
+    assert_eq!(ModuleRtError::from(ConfigError::ZeroCallSlots), ModuleRtError::Config {
+        error: ConfigError::ZeroCallSlots,
+    });
+}
+
+#[test]
+fn module_rt_error_source() {
+    let error = ModuleRtError::from(PoolError::AlreadyCleared);
+    assert_eq!(error.source().unwrap().to_string(), PoolError::AlreadyCleared.to_string());
+    let error = ModuleRtError::from(ModuleError::NotShutDown);
+    assert_eq!(error.source().unwrap().to_string(), ModuleError::NotShutDown.to_string());
+    assert!(ModuleRtError::from(PoolError::ModuleGone).source().is_none());
+}
@@ -17,12 +17,15 @@
 extern crate foundry_module_rt as fmoudle_rt;
 extern crate foundry_process_sandbox as fproc_sndbx;
 
-use fmoudle_rt::coordinator_interface::{FoundryModule, PartialRtoConfig, Port};
-use fmoudle_rt::UserModule;
+#[cfg(windows)]
+use fmoudle_rt::coordinator_interface::NamedPipeEnd;
+use fmoudle_rt::coordinator_interface::{FoundryModule, PartialRtoConfig, Port, TcpEnd, TransportKind};
+use fmoudle_rt::{Cbor, ImportError, ModuleError, PoolError, TypedInit, UserModule};
 use fproc_sndbx::execution::executor::{add_function_pool, execute, Context as ExecutorContext, PlainThread};
 use fproc_sndbx::ipc::{generate_random_name, intra::Intra, Ipc};
 use remote_trait_object::raw_exchange::{import_service_from_handle, HandleToExchange, Skeleton};
 use remote_trait_object::{service, Config as RtoConfig, Context as RtoContext, Service, ServiceToImport};
+use std::net::TcpListener;
 use std::sync::Arc;
 
 #[service]
@@ -51,29 +54,74 @@ struct ModuleA {
     others_greeting: String,
     /// along with expected value from hello()
     hello_list: Vec<(Box<dyn Hello>, i32)>,
+    /// exporter module of each imported service
+    exporters: Vec<String>,
 }
 
-impl UserModule for ModuleA {
-    fn new(arg: &[u8]) -> Self {
-        let (my_greeting, others_greeting): (String, String) = serde_cbor::from_slice(arg).unwrap();
-        Self {
+impl TypedInit for ModuleA {
+    type InitArg = (String, String);
+    type Codec = Cbor;
+
+    fn new_typed(
+        (my_greeting, others_greeting): Self::InitArg,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
             my_greeting,
             others_greeting,
             hello_list: Vec::new(),
-        }
+            exporters: Vec::new(),
+        })
+    }
+}
+
+impl UserModule for ModuleA {
+    fn new(arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::new_decoded(arg)
     }
 
-    fn prepare_service_to_export(&mut self, ctor_name: &str, ctor_arg: &[u8]) -> Skeleton {
-        assert_eq!(ctor_name, "Constructor");
-        let value: i32 = serde_cbor::from_slice(ctor_arg).unwrap();
-        Skeleton::new(Box::new(SimpleHello {
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        if ctor_name != "Constructor" {
+            return Err(format!("Unknown constructor: {}", ctor_name).into())
+        }
+        let value: i32 = serde_cbor::from_slice(ctor_arg)?;
+        Ok(Skeleton::new(Box::new(SimpleHello {
             value,
             greeting: self.my_greeting.clone(),
-        }) as Box<dyn Hello>)
+        }) as Box<dyn Hello>))
     }
 
-    fn import_service(&mut self, rto_context: &RtoContext, name: &str, handle: HandleToExchange) {
-        self.hello_list.push((import_service_from_handle(rto_context, handle), name.parse().unwrap()))
+    fn validate_import(&self, _exporter_module: &str, name: &str) -> Result<(), ImportError> {
+        if name.is_empty() {
+            return Err(ImportError::Rejected {
+                name: name.to_owned(),
+                message: "A service must be named".to_owned(),
+            })
+        }
+        Ok(())
+    }
+
+    fn import_service(
+        &mut self,
+        rto_context: &RtoContext,
+        exporter_module: &str,
+        name: &str,
+        handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        self.hello_list.push((import_service_from_handle(rto_context, handle), name.parse().unwrap()));
+        self.exporters.push(exporter_module.to_owned());
+        Ok(())
+    }
+
+    fn revoke_import(&mut self, _exporter_module: &str, name: &str) {
+        let value: i32 = name.parse().unwrap();
+        if let Some(index) = self.hello_list.iter().position(|(_, v)| *v == value) {
+            self.hello_list.remove(index);
+            self.exporters.remove(index);
+        }
     }
 
     fn debug(&mut self, _arg: &[u8]) -> Vec<u8> {
@@ -81,7 +129,7 @@ impl UserModule for ModuleA {
             assert_eq!(hello.hello(), *value);
             assert_eq!(hello.hi(), self.others_greeting);
         }
-        Vec::new()
+        serde_cbor::to_vec(&self.exporters).unwrap()
     }
 }
 
@@ -89,24 +137,48 @@ fn execute_module<M: UserModule + 'static>(args: Vec<String>) {
     fmoudle_rt::start::<Intra, M>(args);
 }
 
-fn create_module(
+/// Connects to the module without initializing it.
+fn import_module(
     mut ctx: ExecutorContext<Intra, PlainThread>,
-    n: usize,
-    init: &[u8],
 ) -> (ExecutorContext<Intra, PlainThread>, RtoContext, Box<dyn fmoudle_rt::coordinator_interface::FoundryModule>) {
-    let exports: Vec<(String, Vec<u8>)> =
-        (0..n).map(|i| ("Constructor".to_owned(), serde_cbor::to_vec(&i).unwrap())).collect();
-
     let (transport_send, transport_recv) = ctx.ipc.take().unwrap().split();
     let config = RtoConfig::default_setup();
     let (rto_context, module): (_, ServiceToImport<dyn FoundryModule>) =
         remote_trait_object::Context::with_initial_service_import(config, transport_send, transport_recv);
-    let mut module: Box<dyn FoundryModule> = module.into_proxy();
+    (ctx, rto_context, module.into_proxy())
+}
+
+fn create_module(
+    ctx: ExecutorContext<Intra, PlainThread>,
+    n: usize,
+    init: &[u8],
+) -> (ExecutorContext<Intra, PlainThread>, RtoContext, Box<dyn fmoudle_rt::coordinator_interface::FoundryModule>) {
+    let exports: Vec<(String, Vec<u8>)> =
+        (0..n).map(|i| ("Constructor".to_owned(), serde_cbor::to_vec(&i).unwrap())).collect();
 
-    module.initialize(init, &exports);
+    let (ctx, rto_context, mut module) = import_module(ctx);
+    module.initialize(init, &exports).unwrap();
     (ctx, rto_context, module)
 }
 
+/// Creates a port on each module, named after the other side, and initializes both ends of the link.
+fn create_port_pair(
+    (module1, name1): (&mut dyn FoundryModule, &str),
+    (module2, name2): (&mut dyn FoundryModule, &str),
+    (ipc_arg1, ipc_arg2): (Vec<u8>, Vec<u8>),
+    transport: TransportKind,
+) -> (Box<dyn Port>, Box<dyn Port>) {
+    let mut port1: Box<dyn Port> = module1.create_port(name2).unwrap().unwrap_import().into_proxy();
+    let mut port2: Box<dyn Port> = module2.create_port(name1).unwrap().unwrap_import().into_proxy();
+
+    let j = std::thread::spawn(move || {
+        port1.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg1, transport).unwrap();
+        port1
+    });
+    port2.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg2, transport).unwrap();
+    (j.join().unwrap(), port2)
+}
+
 #[test]
 fn pair() {
     let name_1 = generate_random_name();
@@ -124,23 +196,18 @@ fn pair() {
     let (_process2, rto_context2, mut module2) =
         create_module(executor_2, n, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
 
-    let mut port1: Box<dyn Port> = module1.create_port("").unwrap_import().into_proxy();
-    let mut port2: Box<dyn Port> = module2.create_port("").unwrap_import().into_proxy();
-
-    let (ipc_arg1, ipc_arg2) = Intra::arguments_for_both_ends();
-
-    let j = std::thread::spawn(move || {
-        port1.initialize(PartialRtoConfig::from_rto_config(RtoConfig::default_setup()), ipc_arg1, true);
-        port1
-    });
-    port2.initialize(PartialRtoConfig::from_rto_config(RtoConfig::default_setup()), ipc_arg2, true);
-    let mut port1 = j.join().unwrap();
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
 
     let zero_to_n: Vec<usize> = (0..n as usize).collect();
     let zero_to_n_in_string: Vec<String> = (0..n).map(|x| x.to_string()).collect();
 
-    let handles_1_to_2 = port1.export(&zero_to_n);
-    let handles_2_to_1 = port2.export(&zero_to_n);
+    let handles_1_to_2 = port1.export(&zero_to_n).unwrap();
+    let handles_2_to_1 = port2.export(&zero_to_n).unwrap();
 
     assert_eq!(handles_1_to_2.len(), n);
     assert_eq!(handles_2_to_1.len(), n);
@@ -150,18 +217,572 @@ fn pair() {
     let handles_2_to_1: Vec<(String, HandleToExchange)> =
         zero_to_n_in_string.into_iter().zip(handles_2_to_1.into_iter()).collect();
 
-    port1.import(&handles_2_to_1);
-    port2.import(&handles_1_to_2);
+    port1.import(&handles_2_to_1).unwrap();
+    port2.import(&handles_1_to_2).unwrap();
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    module1.debug(&[]).unwrap();
+    module2.debug(&[]).unwrap();
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn initialize_twice() {
+    let name = generate_random_name();
+    add_function_pool(name.clone(), Arc::new(execute_module::<ModuleA>));
+    let executor = execute::<Intra, PlainThread>(&name).unwrap();
+
+    let init = serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap();
+    let (_process, rto_context, mut module) = create_module(executor, 1, &init);
+
+    assert_eq!(module.initialize(&init, &[]), Err(ModuleError::AlreadyInitialized));
+
+    module.shutdown().unwrap();
+    rto_context.disable_garbage_collection();
+}
+
+#[test]
+fn export_out_of_range() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, 3, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 3, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, _port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    assert!(matches!(
+        port1.export(&[5]),
+        Err(PoolError::IndexOutOfRange {
+            index: 5,
+            len: 3
+        })
+    ));
+    assert_eq!(port1.export(&[0, 1, 2]).unwrap().len(), 3);
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn export_all() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, 3, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 3, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    let handles = port1.export_all().unwrap();
+    assert_eq!(handles.len(), 3);
+    let slots: Vec<(String, HandleToExchange)> =
+        handles.into_iter().enumerate().map(|(i, handle)| (i.to_string(), handle)).collect();
+    assert_eq!(port2.import(&slots).unwrap(), vec!["0".to_owned(), "1".to_owned(), "2".to_owned()]);
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // Each service answers with its index, so it checks that the handles are in the order of the pool.
+    module2.debug(&[]).unwrap();
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn export_keys() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) = import_module(executor_1);
+    let init = serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap();
+    let export =
+        |key: &str, value: i32| (key.to_owned(), "Constructor".to_owned(), serde_cbor::to_vec(&value).unwrap());
+    assert_eq!(
+        module1.initialize_with_keys(&init, &[export("one", 1), export("one", 2)]),
+        Err(ModuleError::DuplicateExportKey {
+            key: "one".to_owned()
+        })
+    );
+    // The keys are declared in an order different from the one they are exported in.
+    module1.initialize_with_keys(&init, &[export("two", 2), export("zero", 0), export("one", 1)]).unwrap();
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 0, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    assert_eq!(
+        port1.export_keys(&["three".to_owned()]),
+        Err(PoolError::UnknownKey {
+            key: "three".to_owned()
+        })
+    );
+    let keys = vec!["zero".to_owned(), "one".to_owned(), "two".to_owned()];
+    let handles = port1.export_keys(&keys).unwrap();
+    let slots: Vec<(String, HandleToExchange)> =
+        handles.into_iter().enumerate().map(|(i, handle)| (i.to_string(), handle)).collect();
+    port2.import(&slots).unwrap();
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // Each service answers with the value that it has been imported with.
+    module2.debug(&[]).unwrap();
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn partially_failed_exports() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) = import_module(executor_1);
+    let exports = vec![
+        ("Constructor".to_owned(), serde_cbor::to_vec(&0).unwrap()),
+        ("Bogus".to_owned(), Vec::new()),
+        ("Constructor".to_owned(), serde_cbor::to_vec(&2).unwrap()),
+    ];
+    match module1.initialize(&serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap(), &exports) {
+        Err(ModuleError::ExportPreparationFailed {
+            errors,
+        }) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].index, 1);
+            assert_eq!(errors[0].ctor_name, "Bogus");
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 3, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, _port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    assert_eq!(port1.export(&[0, 2]).unwrap().len(), 2);
+    assert!(matches!(
+        port1.export(&[1]),
+        Err(PoolError::NotPrepared {
+            index: 1
+        })
+    ));
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn exporter_module_name() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let n = 2;
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, n, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, n, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "Seoul"),
+        (&mut *module2, "Tokyo"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    let names: Vec<String> = (0..n).map(|x| x.to_string()).collect();
+    let handles_1_to_2 = port1.export(&[0, 1]).unwrap();
+    let handles_2_to_1 = port2.export(&[0, 1]).unwrap();
+    port1.import(&names.iter().cloned().zip(handles_2_to_1.into_iter()).collect::<Vec<_>>()).unwrap();
+    port2.import(&names.iter().cloned().zip(handles_1_to_2.into_iter()).collect::<Vec<_>>()).unwrap();
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    let exporters1: Vec<String> = serde_cbor::from_slice(&module1.debug(&[]).unwrap()).unwrap();
+    let exporters2: Vec<String> = serde_cbor::from_slice(&module2.debug(&[]).unwrap()).unwrap();
+    assert_eq!(exporters1, vec!["Tokyo"; n]);
+    assert_eq!(exporters2, vec!["Seoul"; n]);
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn pair_over_tcp() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let n = 3;
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, n, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, n, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    // Borrow a free port from the OS.
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        TcpEnd::arguments_for_both_ends(addr),
+        TransportKind::Tcp,
+    );
+
+    let names: Vec<String> = (0..n).map(|x| x.to_string()).collect();
+    let handles_1_to_2 = port1.export(&[0, 1, 2]).unwrap();
+    let handles_2_to_1 = port2.export(&[0, 1, 2]).unwrap();
+    port1.import(&names.iter().cloned().zip(handles_2_to_1.into_iter()).collect::<Vec<_>>()).unwrap();
+    port2.import(&names.iter().cloned().zip(handles_1_to_2.into_iter()).collect::<Vec<_>>()).unwrap();
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // Each module checks the imported services over TCP.
+    module1.debug(&[]).unwrap();
+    module2.debug(&[]).unwrap();
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn reconnect_over_tcp() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_3 = generate_random_name();
+    add_function_pool(name_3.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+    let executor_3 = execute::<Intra, PlainThread>(&name_3).unwrap();
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, 1, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 0, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+    // module3 takes over the link of module2, as if module2 had restarted.
+    let (_process3, rto_context3, mut module3) =
+        create_module(executor_3, 0, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        TcpEnd::arguments_for_both_ends(addr),
+        TransportKind::Tcp,
+    );
+    let handles = port1.export(&[0]).unwrap();
+    port2.import(&[("0".to_owned(), handles[0])]).unwrap();
+
+    // The socket is severed as module2 goes away.
+    module2.shutdown().unwrap();
+    rto_context2.disable_garbage_collection();
+
+    let mut port3: Box<dyn Port> = module3.create_port("module1").unwrap().unwrap_import().into_proxy();
+    assert_eq!(
+        port3.reconnect(Vec::new()),
+        Err(ModuleError::PortNotInitialized {
+            name: "module1".to_owned()
+        })
+    );
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (ipc_arg1, ipc_arg3) = TcpEnd::arguments_for_both_ends(addr);
+    let join = std::thread::spawn(move || {
+        port1.reconnect(ipc_arg1).unwrap();
+        port1
+    });
+    port3.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg3, TransportKind::Tcp).unwrap();
+    let mut port1 = join.join().unwrap();
+
+    // The service is exchanged again over the new link.
+    let handles = port1.export(&[0]).unwrap();
+    port3.import(&[("0".to_owned(), handles[0])]).unwrap();
+    module1.finish_bootstrap().unwrap();
+    module3.finish_bootstrap().unwrap();
+
+    let exporters: Vec<String> = serde_cbor::from_slice(&module3.debug(&[]).unwrap()).unwrap();
+    assert_eq!(exporters, vec!["module1".to_owned()]);
+
+    module1.shutdown().unwrap();
+    module3.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context3.disable_garbage_collection();
+}
+
+#[cfg(windows)]
+#[test]
+fn pair_over_named_pipe() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let n = 3;
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, n, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, n, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        NamedPipeEnd::arguments_for_both_ends(&generate_random_name()),
+        TransportKind::NamedPipe,
+    );
 
-    module1.finish_bootstrap();
-    module2.finish_bootstrap();
+    let names: Vec<String> = (0..n).map(|x| x.to_string()).collect();
+    let handles_1_to_2 = port1.export(&[0, 1, 2]).unwrap();
+    let handles_2_to_1 = port2.export(&[0, 1, 2]).unwrap();
+    port1.import(&names.iter().cloned().zip(handles_2_to_1.into_iter()).collect::<Vec<_>>()).unwrap();
+    port2.import(&names.iter().cloned().zip(handles_1_to_2.into_iter()).collect::<Vec<_>>()).unwrap();
 
-    module1.debug(&[]);
-    module2.debug(&[]);
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
 
-    module1.shutdown();
-    module2.shutdown();
+    // Each module checks the imported services over the named pipes.
+    module1.debug(&[]).unwrap();
+    module2.debug(&[]).unwrap();
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
 
     rto_context1.disable_garbage_collection();
     rto_context2.disable_garbage_collection();
 }
+
+#[test]
+fn export_by_index_and_name() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, 1, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 2, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    // module1 has only one "Constructor", while module2 has two.
+    let by_name = port1.export_named(&["Constructor".to_owned()]).unwrap();
+    let by_index = port2.export(&[1]).unwrap();
+    assert!(matches!(port2.export_named(&["Constructor".to_owned()]), Err(PoolError::AmbiguousName { .. })));
+    assert!(matches!(port1.export_named(&["Bogus".to_owned()]), Err(PoolError::UnknownName { .. })));
+
+    port2.import(&[("0".to_owned(), by_name[0])]).unwrap();
+    port1.import(&[("1".to_owned(), by_index[0])]).unwrap();
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // Each module checks the values of the imported services.
+    module1.debug(&[]).unwrap();
+    module2.debug(&[]).unwrap();
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn revoke_import() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, 2, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 0, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    // ModuleA accepts any name up front, but panics at the last slot, whose name is not the expected value.
+    let handles = port1.export(&[0, 1]).unwrap();
+    match port2.import(&[("0".to_owned(), handles[0]), ("one".to_owned(), handles[1])]) {
+        Err(ImportError::Failed {
+            errors,
+        }) => {
+            assert!(matches!(errors.as_slice(), [ImportError::Panicked { name, .. }] if name == "one"));
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(port2.stats().imported, 0);
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // The first slot, imported before the failure, has been revoked.
+    let exporters: Vec<String> = serde_cbor::from_slice(&module2.debug(&[]).unwrap()).unwrap();
+    assert!(exporters.is_empty());
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn import_transactionally() {
+    let name_1 = generate_random_name();
+    add_function_pool(name_1.clone(), Arc::new(execute_module::<ModuleA>));
+    let name_2 = generate_random_name();
+    add_function_pool(name_2.clone(), Arc::new(execute_module::<ModuleA>));
+
+    let executor_1 = execute::<Intra, PlainThread>(&name_1).unwrap();
+    let executor_2 = execute::<Intra, PlainThread>(&name_2).unwrap();
+
+    let (_process1, rto_context1, mut module1) =
+        create_module(executor_1, 2, &serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap());
+    let (_process2, rto_context2, mut module2) =
+        create_module(executor_2, 0, &serde_cbor::to_vec(&("Konnichiwa", "Annyeong")).unwrap());
+
+    let (mut port1, mut port2) = create_port_pair(
+        (&mut *module1, "module1"),
+        (&mut *module2, "module2"),
+        Intra::arguments_for_both_ends(),
+        TransportKind::Intra,
+    );
+
+    // ModuleA rejects a service without a name before importing any.
+    let handles = port1.export(&[0, 1]).unwrap();
+    match port2.import(&[("0".to_owned(), handles[0]), ("".to_owned(), handles[1])]) {
+        Err(ImportError::Failed {
+            errors,
+        }) => {
+            assert!(matches!(errors.as_slice(), [ImportError::Rejected { name, .. }] if name.is_empty()));
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(port2.stats().imported, 0);
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // It reports the exporters of the imported services.
+    let exporters: Vec<String> = serde_cbor::from_slice(&module2.debug(&[]).unwrap()).unwrap();
+    assert!(exporters.is_empty());
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+
+    rto_context1.disable_garbage_collection();
+    rto_context2.disable_garbage_collection();
+}
+
+#[test]
+fn typed_init_arg() {
+    let module = ModuleA::new(&serde_cbor::to_vec(&("Annyeong", "Konnichiwa")).unwrap()).unwrap();
+    assert_eq!(module.my_greeting, "Annyeong");
+    assert_eq!(module.others_greeting, "Konnichiwa");
+
+    assert!(ModuleA::new(&serde_cbor::to_vec(&0).unwrap()).is_err());
+}
@@ -0,0 +1,1846 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+extern crate foundry_process_sandbox as fproc_sndbx;
+
+use fmoudle_rt::coordinator_interface::{
+    Compression, FoundryModule, LinkHealth, LinkStatus, ModuleRuntimeConfig, ModuleState, PartialRtoConfig, Port,
+    PortInfo, PortStats, SharedMemoryEnd, ShutdownOutcome, TcpEnd, TransportKind, PORT_PROTOCOL_VERSION,
+};
+use fmoudle_rt::{
+    ImportError, ModuleConfig, ModuleError, ModuleEvent, ModuleHandle, Ping, PoolError, RtoConfigHook, ShutdownWaiter,
+    ThreadPoolHandle, UserModule,
+};
+use fproc_sndbx::execution::executor::{add_function_pool, execute, Context as ExecutorContext, PlainThread};
+use fproc_sndbx::ipc::{generate_random_name, intra::Intra, Ipc};
+use remote_trait_object::raw_exchange::{import_service_from_handle, HandleToExchange, Skeleton};
+use remote_trait_object::transport::{Terminate, TransportError, TransportSend};
+use remote_trait_object::{
+    service, Config as RtoConfig, Context as RtoContext, Service, ServiceToExport, ServiceToImport,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[service]
+trait Token: Service {
+    fn value(&self) -> usize;
+    /// Name of the thread that serves the call
+    fn worker(&self) -> Option<String>;
+    /// A payload of `len` bytes, which is easy to compress
+    fn payload(&self, len: usize) -> Vec<u8>;
+}
+
+struct SimpleToken {
+    value: usize,
+    calls: Arc<AtomicUsize>,
+    /// How long it takes to answer
+    call_delay: Duration,
+    /// How long it takes to be dropped
+    drop_delay: Duration,
+}
+impl Service for SimpleToken {}
+impl Drop for SimpleToken {
+    fn drop(&mut self) {
+        std::thread::sleep(self.drop_delay);
+    }
+}
+impl Token for SimpleToken {
+    fn value(&self) -> usize {
+        std::thread::sleep(self.call_delay);
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.value
+    }
+
+    fn worker(&self) -> Option<String> {
+        std::thread::current().name().map(ToOwned::to_owned)
+    }
+
+    fn payload(&self, len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+}
+
+/// What `Counter` reports on `debug()` with an empty argument, or after a while with `b"sleep"`,
+/// while it panics with any other one.
+#[derive(Serialize, Deserialize)]
+struct Report {
+    imports_at_bootstrap: Option<usize>,
+    /// Number of calls served by the exported tokens
+    calls: usize,
+    /// Values of the tokens that have been prepared
+    prepared: Vec<usize>,
+    /// Whether the job submitted at the end of the bootstrap has run
+    background_done: bool,
+    /// Names of the peers that the ports have been created for, in order
+    peers: Vec<String>,
+}
+
+/// Counts the imported services and records the count at the end of the bootstrap.
+struct Counter {
+    tokens: Vec<Box<dyn Token>>,
+    /// Module that each of `tokens` has been imported from
+    exporters: Vec<String>,
+    /// Names that the tokens have been imported under, along with their indices in `tokens`
+    names: Vec<(String, usize)>,
+    imports_at_bootstrap: Option<usize>,
+    calls: Arc<AtomicUsize>,
+    prepared: Vec<usize>,
+    /// Whether to call every imported token while shutting down
+    farewell: bool,
+    background_done: Arc<AtomicBool>,
+    /// Whether to reject the shutdown
+    busy: bool,
+    /// Tokens made by `make_token`, waiting to be exported
+    dynamic_exports: Vec<(String, Skeleton)>,
+    peers: Vec<String>,
+}
+
+impl UserModule for Counter {
+    fn new(arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
+            tokens: Vec::new(),
+            exporters: Vec::new(),
+            names: Vec::new(),
+            imports_at_bootstrap: None,
+            calls: Default::default(),
+            prepared: Vec::new(),
+            farewell: serde_cbor::from_slice(arg)?,
+            background_done: Default::default(),
+            busy: false,
+            dynamic_exports: Vec::new(),
+            peers: Vec::new(),
+        })
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        let value: usize = serde_cbor::from_slice(ctor_arg)?;
+        self.prepared.push(value);
+        let (call_delay, drop_delay) = match ctor_name {
+            "SlowToken" => (Duration::from_millis(200), Duration::from_secs(1)),
+            "LongCallToken" => (Duration::from_millis(500), Duration::default()),
+            _ => Default::default(),
+        };
+        Ok(Skeleton::new(Box::new(SimpleToken {
+            value,
+            calls: Arc::clone(&self.calls),
+            call_delay,
+            drop_delay,
+        }) as Box<dyn Token>))
+    }
+
+    fn imported_count(&self) -> usize {
+        self.tokens.len()
+    }
+
+    fn imported_names(&self) -> Vec<String> {
+        self.names.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    fn retained_proxy_count(&self) -> usize {
+        self.tokens.len()
+    }
+
+    fn port_created(&mut self, peer_name: &str) {
+        self.peers.push(peer_name.to_owned());
+    }
+
+    fn take_dynamic_export(&mut self, key: &str) -> Option<Skeleton> {
+        let index = self.dynamic_exports.iter().position(|(made, _)| made == key)?;
+        Some(self.dynamic_exports.remove(index).1)
+    }
+
+    fn import_service(
+        &mut self,
+        rto_context: &RtoContext,
+        exporter_module: &str,
+        name: &str,
+        handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        self.names.push((name.to_owned(), self.tokens.len()));
+        self.tokens.push(import_service_from_handle(rto_context, handle));
+        self.exporters.push(exporter_module.to_owned());
+        Ok(())
+    }
+
+    fn alias_import(&mut self, _exporter_module: &str, name: &str, alias: &str) -> Result<(), ImportError> {
+        let index = self.names.iter().find(|(imported, _)| imported == name).unwrap().1;
+        self.names.push((alias.to_owned(), index));
+        Ok(())
+    }
+
+    fn ping_imports(&mut self) -> Vec<(String, Ping<'_>)> {
+        self.exporters
+            .iter()
+            .cloned()
+            .zip(self.tokens.iter())
+            .map(|(exporter, token)| {
+                (
+                    exporter,
+                    Box::new(move || {
+                        token.value();
+                    }) as Ping<'_>,
+                )
+            })
+            .collect()
+    }
+
+    fn bootstrap_finished(&mut self, thread_pool: ThreadPoolHandle) {
+        self.imports_at_bootstrap = Some(self.tokens.len());
+        let background_done = Arc::clone(&self.background_done);
+        thread_pool.execute(move || background_done.store(true, Ordering::SeqCst));
+    }
+
+    fn can_shutdown(&self) -> bool {
+        !self.busy
+    }
+
+    fn shutting_down(&mut self) {
+        if !self.farewell {
+            return
+        }
+        for token in &self.tokens {
+            token.value();
+        }
+    }
+
+    fn call(&mut self, method: &str, arg: &[u8]) -> Result<Vec<u8>, ModuleError> {
+        match method {
+            "add_calls" => {
+                let n: usize = serde_cbor::from_slice(arg).unwrap();
+                Ok(serde_cbor::to_vec(&(self.calls.fetch_add(n, Ordering::SeqCst) + n)).unwrap())
+            }
+            "call_tokens" => {
+                for token in &self.tokens {
+                    token.value();
+                }
+                Ok(serde_cbor::to_vec(&self.tokens.len()).unwrap())
+            }
+            "check_payloads" => {
+                let len: usize = serde_cbor::from_slice(arg).unwrap();
+                let valid = self.tokens.iter().all(|token| {
+                    let payload = token.payload(len);
+                    payload.len() == len && payload.iter().enumerate().all(|(i, &byte)| byte == (i % 251) as u8)
+                });
+                Ok(serde_cbor::to_vec(&valid).unwrap())
+            }
+            "make_token" => {
+                let (key, value): (String, usize) = serde_cbor::from_slice(arg).unwrap();
+                let skeleton = Skeleton::new(Box::new(SimpleToken {
+                    value,
+                    calls: Arc::clone(&self.calls),
+                    call_delay: Duration::default(),
+                    drop_delay: Duration::default(),
+                }) as Box<dyn Token>);
+                self.dynamic_exports.push((key, skeleton));
+                Ok(Vec::new())
+            }
+            "drop_tokens" => {
+                self.tokens.clear();
+                self.exporters.clear();
+                self.names.clear();
+                Ok(Vec::new())
+            }
+            "set_busy" => {
+                self.busy = serde_cbor::from_slice(arg).unwrap();
+                Ok(Vec::new())
+            }
+            "named_values" => {
+                let values: Vec<(String, usize)> =
+                    self.names.iter().map(|(name, index)| (name.clone(), self.tokens[*index].value())).collect();
+                Ok(serde_cbor::to_vec(&values).unwrap())
+            }
+            "token_workers" => {
+                let workers: Vec<Option<String>> = self.tokens.iter().map(|token| token.worker()).collect();
+                Ok(serde_cbor::to_vec(&workers).unwrap())
+            }
+            _ => Err(ModuleError::UnknownMethod {
+                method: method.to_owned(),
+            }),
+        }
+    }
+
+    fn debug(&mut self, arg: &[u8]) -> Vec<u8> {
+        if arg == b"sleep" {
+            std::thread::sleep(Duration::from_millis(500));
+        } else if !arg.is_empty() {
+            panic!("{}", String::from_utf8_lossy(arg))
+        }
+        serde_cbor::to_vec(&Report {
+            imports_at_bootstrap: self.imports_at_bootstrap,
+            calls: self.calls.load(Ordering::SeqCst),
+            prepared: self.prepared.clone(),
+            background_done: self.background_done.load(Ordering::SeqCst),
+            peers: self.peers.clone(),
+        })
+        .unwrap()
+    }
+}
+
+/// Counts the messages that the coordinator sends to a module.
+struct CountingSend<S> {
+    inner: S,
+    sent: Arc<AtomicUsize>,
+}
+
+impl<S: TransportSend> TransportSend for CountingSend<S> {
+    fn send(&self, data: &[u8], timeout: Option<Duration>) -> Result<(), TransportError> {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        self.inner.send(data, timeout)
+    }
+
+    fn create_terminator(&self) -> Box<dyn Terminate> {
+        self.inner.create_terminator()
+    }
+}
+
+struct Module {
+    module: Box<dyn FoundryModule>,
+    rto_context: RtoContext,
+    /// Number of messages sent to the module
+    sent: Arc<AtomicUsize>,
+    _process: ExecutorContext<Intra, PlainThread>,
+}
+
+impl Module {
+    fn report(&mut self) -> Report {
+        serde_cbor::from_slice(&self.module.debug(&[]).unwrap()).unwrap()
+    }
+
+    fn shutdown(mut self) {
+        self.module.shutdown().unwrap();
+        self.rto_context.disable_garbage_collection();
+    }
+}
+
+/// Runs `f` as a module and connects to it, without initializing it.
+fn run_module<F: Fn(Vec<String>) + Send + Sync + 'static>(f: F) -> Module {
+    let name = generate_random_name();
+    add_function_pool(name.clone(), Arc::new(f));
+    let mut process = execute::<Intra, PlainThread>(&name).unwrap();
+
+    let (transport_send, transport_recv) = process.ipc.take().unwrap().split();
+    let sent = Arc::new(AtomicUsize::new(0));
+    let transport_send = CountingSend {
+        inner: transport_send,
+        sent: Arc::clone(&sent),
+    };
+    let (rto_context, module): (_, ServiceToImport<dyn FoundryModule>) =
+        remote_trait_object::Context::with_initial_service_import(
+            RtoConfig::default_setup(),
+            transport_send,
+            transport_recv,
+        );
+    Module {
+        module: module.into_proxy(),
+        rto_context,
+        sent,
+        _process: process,
+    }
+}
+
+/// Runs a module that exports `n` tokens.
+fn create_module<M: UserModule + 'static>(init: &[u8], n: usize) -> Module {
+    create_module_with_config::<M>(ModuleConfig::default(), init, n)
+}
+
+fn create_module_with_config<M: UserModule + 'static>(config: ModuleConfig, init: &[u8], n: usize) -> Module {
+    let mut module = run_module(move |args| fmoudle_rt::start_with_config::<Intra, M>(args, config.clone()));
+    let exports: Vec<(String, Vec<u8>)> =
+        (0..n).map(|i| ("Token".to_owned(), serde_cbor::to_vec(&i).unwrap())).collect();
+    module.module.initialize(init, &exports).unwrap();
+    module
+}
+
+/// Creates a port on each module, named after the other side, and links them.
+fn create_port_pair(
+    (module1, name1): (&mut Module, &str),
+    (module2, name2): (&mut Module, &str),
+) -> (Box<dyn Port>, Box<dyn Port>) {
+    let port1: Box<dyn Port> = module1.module.create_port(name2).unwrap().unwrap_import().into_proxy();
+    let port2: Box<dyn Port> = module2.module.create_port(name1).unwrap().unwrap_import().into_proxy();
+    initialize_port_pair(port1, port2)
+}
+
+/// Initializes both ends of a link.
+fn initialize_port_pair(port1: Box<dyn Port>, port2: Box<dyn Port>) -> (Box<dyn Port>, Box<dyn Port>) {
+    initialize_port_pair_with_config(port1, port2, PartialRtoConfig::builder().build().unwrap())
+}
+
+fn initialize_port_pair_with_config(
+    mut port1: Box<dyn Port>,
+    mut port2: Box<dyn Port>,
+    rto_config: PartialRtoConfig,
+) -> (Box<dyn Port>, Box<dyn Port>) {
+    let (ipc_arg1, ipc_arg2) = Intra::arguments_for_both_ends();
+
+    let rto_config1 = rto_config.clone();
+    let join = std::thread::spawn(move || {
+        port1.initialize(rto_config1, ipc_arg1, TransportKind::Intra).unwrap();
+        port1
+    });
+    port2.initialize(rto_config, ipc_arg2, TransportKind::Intra).unwrap();
+    (join.join().unwrap(), port2)
+}
+
+/// Connects to a port in a new thread, only to answer the handshake with `version`.
+fn fake_peer(ipc_arg: Vec<u8>, version: u32) -> std::thread::JoinHandle<impl Send> {
+    std::thread::spawn(move || {
+        let (transport_send, transport_recv) = Intra::new(ipc_arg).split();
+        TransportSend::send(&transport_send, &version.to_be_bytes(), None).unwrap();
+        (transport_send, transport_recv)
+    })
+}
+
+/// Exports the first `n` services of each side to the other.
+fn exchange(port1: &mut dyn Port, port2: &mut dyn Port, n: usize) {
+    let ids: Vec<usize> = (0..n).collect();
+    let names: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+
+    let handles_1_to_2 = port1.export(&ids).unwrap();
+    let handles_2_to_1 = port2.export(&ids).unwrap();
+
+    port1.import(&names.iter().cloned().zip(handles_2_to_1.into_iter()).collect::<Vec<_>>()).unwrap();
+    port2.import(&names.iter().cloned().zip(handles_1_to_2.into_iter()).collect::<Vec<_>>()).unwrap();
+}
+
+#[test]
+fn bootstrap_finished_hook() {
+    let n = 3;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+
+    assert_eq!(module1.report().imports_at_bootstrap, None);
+
+    exchange(&mut *port1, &mut *port2, n);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    assert_eq!(module1.report().imports_at_bootstrap, Some(n));
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn shutting_down_hook() {
+    let n = 3;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&true).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, n);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    // module1 calls every token of module2 while shutting down.
+    module1.shutdown();
+    assert_eq!(module2.report().calls, n);
+
+    module2.shutdown();
+}
+
+#[test]
+fn shutdown_twice() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module =
+        fmoudle_rt::create_foundry_module(module, &[("Token".to_owned(), serde_cbor::to_vec(&0).unwrap())]);
+
+    module.shutdown().unwrap();
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn stop_with_handle() {
+    let (handle_send, handle_recv) = crossbeam::channel::bounded(1);
+    let module = run_module(move |args| {
+        handle_send.send(fmoudle_rt::start_with_handle::<Intra, Counter>(args, ModuleConfig::default())).unwrap();
+    });
+    let Module {
+        mut module,
+        rto_context,
+        ..
+    } = module;
+    module.initialize(&serde_cbor::to_vec(&false).unwrap(), &[]).unwrap();
+
+    let handle: ModuleHandle = handle_recv.recv().unwrap();
+    handle.stop();
+
+    // The module is gone without the coordinator calling shutdown.
+    rto_context.disable_garbage_collection();
+}
+
+#[test]
+fn stop_before_serving() {
+    let (stopped_send, stopped_recv) = crossbeam::channel::bounded(1);
+    let module = run_module(move |args| {
+        // The module is stopped while its thread is still connecting to the coordinator.
+        fmoudle_rt::start_with_handle::<Intra, Counter>(args, ModuleConfig::default()).stop();
+        stopped_send.send(()).unwrap();
+    });
+    let Module {
+        module,
+        rto_context,
+        ..
+    } = module;
+    stopped_recv.recv_timeout(Duration::from_secs(10)).expect("The module has never stopped");
+
+    rto_context.disable_garbage_collection();
+    drop(module);
+}
+
+#[test]
+fn wait_detached() {
+    let (waiter_send, waiter_recv) = crossbeam::channel::bounded(1);
+    let mut module = run_module(move |args| {
+        waiter_send.send(fmoudle_rt::start_detached::<Intra, Counter>(args, ModuleConfig::default())).unwrap();
+    });
+    module.module.initialize(&serde_cbor::to_vec(&false).unwrap(), &[]).unwrap();
+
+    let waiter: ShutdownWaiter = waiter_recv.recv().unwrap();
+    assert!(!waiter.try_wait());
+    module.shutdown();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !waiter.try_wait() {
+        assert!(Instant::now() < deadline, "The module has never finished");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    waiter.wait();
+}
+
+#[test]
+fn lazy_exports() {
+    let config = ModuleConfig {
+        lazy_exports: true,
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 3);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 3);
+    assert!(module1.report().prepared.is_empty());
+    assert_eq!(module2.report().prepared, vec![0, 1, 2]);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let handles_1_to_2 = port1.export(&[0, 2]).unwrap();
+    port2.import(&[("0".to_owned(), handles_1_to_2[0]), ("2".to_owned(), handles_1_to_2[1])]).unwrap();
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    // The token that was never exported is never constructed.
+    assert_eq!(module1.report().prepared, vec![0, 2]);
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn strict_exports() {
+    let config = ModuleConfig {
+        strict_exports: true,
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 2);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let handles_1_to_2 = port1.export(&[0]).unwrap();
+    port2.import(&[("0".to_owned(), handles_1_to_2[0])]).unwrap();
+    // The second token is given to module1, but no peer takes it.
+    assert_eq!(
+        module1.module.finish_bootstrap(),
+        Err(ModuleError::UnexportedServices {
+            keys: vec!["1".to_owned()],
+        })
+    );
+    module2.module.finish_bootstrap().unwrap();
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn panic_in_debug() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let error = module.module.debug(b"boom").unwrap_err();
+    assert_eq!(error, ModuleError::Panicked {
+        message: "boom".to_owned()
+    });
+    // The module keeps serving after the panic.
+    assert_eq!(module.report().calls, 0);
+
+    module.shutdown();
+}
+
+#[test]
+fn call_method() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let calls = module.module.call("add_calls", &serde_cbor::to_vec(&3usize).unwrap()).unwrap();
+    assert_eq!(serde_cbor::from_slice::<usize>(&calls).unwrap(), 3);
+    assert_eq!(module.report().calls, 3);
+    assert_eq!(module.module.call("remove_calls", &[]).unwrap_err(), ModuleError::UnknownMethod {
+        method: "remove_calls".to_owned()
+    });
+
+    module.shutdown();
+}
+
+#[test]
+fn dynamic_port() {
+    let n = 2;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, n);
+    // A dynamic port is only for a link added after the bootstrap.
+    assert_eq!(module1.module.create_dynamic_port("module3").err(), Some(ModuleError::BootstrapNotFinished));
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    // module3 joins after module1 has finished the bootstrap.
+    assert_eq!(module1.module.create_port("module3").err(), Some(ModuleError::BootstrapAlreadyFinished));
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let port1: Box<dyn Port> = module1.module.create_dynamic_port("module3").unwrap().unwrap_import().into_proxy();
+    let port3: Box<dyn Port> = module3.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let (mut port1, mut port3) = initialize_port_pair(port1, port3);
+    exchange(&mut *port1, &mut *port3, n);
+    module3.module.finish_bootstrap().unwrap();
+
+    let called = module1.module.call("call_tokens", &[]).unwrap();
+    assert_eq!(serde_cbor::from_slice::<usize>(&called).unwrap(), 2 * n);
+    assert_eq!(module2.report().calls, n);
+    assert_eq!(module3.report().calls, n);
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn dynamic_port_after_shutdown() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &[]);
+    module.finish_bootstrap().unwrap();
+    module.shutdown().unwrap();
+    assert_eq!(module.create_dynamic_port("peer").err(), Some(ModuleError::ShutDown));
+}
+
+#[test]
+fn retain_exports() {
+    let config = ModuleConfig {
+        retain_exports: true,
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let handles = port1.export(&[0]).unwrap();
+    port2.import(&[("0".to_owned(), handles[0])]).unwrap();
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    assert_eq!(module1.module.exported_count(), 1);
+
+    // module3 joins after the bootstrap, and gets the same token as module2.
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let port1: Box<dyn Port> = module1.module.create_dynamic_port("module3").unwrap().unwrap_import().into_proxy();
+    let port3: Box<dyn Port> = module3.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let (mut port1, mut port3) = initialize_port_pair(port1, port3);
+    let handles = port1.export(&[0]).unwrap();
+    port3.import(&[("0".to_owned(), handles[0])]).unwrap();
+    module3.module.finish_bootstrap().unwrap();
+
+    module2.module.call("call_tokens", &[]).unwrap();
+    module3.module.call("call_tokens", &[]).unwrap();
+    let report = module1.report();
+    assert_eq!(report.calls, 2);
+    // It has been prepared only once.
+    assert_eq!(report.prepared, vec![0]);
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn shutdown_timeout() {
+    // module1 exports a token that takes a while to be dropped.
+    let mut module1 = run_module(|args| fmoudle_rt::start::<Intra, Counter>(args));
+    module1
+        .module
+        .initialize(&serde_cbor::to_vec(&false).unwrap(), &[("SlowToken".to_owned(), serde_cbor::to_vec(&0).unwrap())])
+        .unwrap();
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    assert_eq!(module1.module.shutdown_with_timeout(Duration::from_millis(100)), ShutdownOutcome::Forced {
+        undrained_ports: vec!["module2".to_owned()]
+    });
+    module1.rto_context.disable_garbage_collection();
+    assert_eq!(module2.module.shutdown_with_timeout(Duration::from_secs(10)), ShutdownOutcome::Clean);
+    module2.rto_context.disable_garbage_collection();
+}
+
+#[test]
+fn port_stats() {
+    let n = 3;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    assert_eq!(port1.stats(), PortStats::default());
+    exchange(&mut *port1, &mut *port2, n);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let before = port1.stats();
+    assert_eq!(before.exported, n);
+    assert_eq!(before.imported, n);
+
+    // module2 calls every token of module1, each of which is a request and a reply for port1.
+    module2.module.call("call_tokens", &[]).unwrap();
+    let after = port1.stats();
+    assert_eq!(after.received_messages - before.received_messages, n);
+    assert_eq!(after.sent_messages - before.sent_messages, n);
+    assert!(after.received_bytes > before.received_bytes);
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn link_three() {
+    let n = 2;
+    let mut modules: Vec<Module> =
+        (0..3).map(|_| create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n)).collect();
+
+    let names = ["module0", "module1", "module2"];
+    let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+        .iter()
+        .cloned()
+        .zip(modules.iter_mut().map(|module| &mut *module.module as &mut dyn FoundryModule))
+        .collect();
+    fmoudle_rt::link_all(&mut ends, |_, _| (0..n).map(|i| (i, i.to_string())).collect(), TransportKind::Intra).unwrap();
+    for module in modules.iter_mut() {
+        module.module.finish_bootstrap().unwrap();
+    }
+
+    // Each module calls the tokens from the other two.
+    let called = modules[0].module.call("call_tokens", &[]).unwrap();
+    assert_eq!(serde_cbor::from_slice::<usize>(&called).unwrap(), 2 * n);
+    assert_eq!(modules[1].report().calls, n);
+    assert_eq!(modules[2].report().calls, n);
+
+    for module in modules {
+        module.shutdown();
+    }
+}
+
+#[test]
+fn call_timeout() {
+    // module1 exports a token that takes a while to answer, to module2 and module3.
+    let mut module1 = run_module(|args| fmoudle_rt::start::<Intra, Counter>(args));
+    module1
+        .module
+        .initialize(&serde_cbor::to_vec(&false).unwrap(), &[("SlowToken".to_owned(), serde_cbor::to_vec(&0).unwrap())])
+        .unwrap();
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    // Each link has its own timeout.
+    let port1_2: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let port2: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let rto_config = PartialRtoConfig::builder().call_timeout(Some(Duration::from_millis(10))).build().unwrap();
+    let (mut port1_2, mut port2) = initialize_port_pair_with_config(port1_2, port2, rto_config);
+    port2.import(&[("0".to_owned(), port1_2.export(&[0]).unwrap()[0])]).unwrap();
+
+    let port1_3: Box<dyn Port> = module1.module.create_port("module3").unwrap().unwrap_import().into_proxy();
+    let port3: Box<dyn Port> = module3.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let rto_config = PartialRtoConfig::builder().call_timeout(Some(Duration::from_secs(10))).build().unwrap();
+    let (mut port1_3, mut port3) = initialize_port_pair_with_config(port1_3, port3, rto_config);
+    port3.import(&[("0".to_owned(), port1_3.export(&[0]).unwrap()[0])]).unwrap();
+
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    module3.module.finish_bootstrap().unwrap();
+
+    match module2.module.call("call_tokens", &[]) {
+        Err(ModuleError::Panicked {
+            ..
+        }) => (),
+        result => panic!("The call must have timed out: {:?}", result),
+    }
+    let called = module3.module.call("call_tokens", &[]).unwrap();
+    assert_eq!(serde_cbor::from_slice::<usize>(&called).unwrap(), 1);
+
+    module2.shutdown();
+    module3.shutdown();
+    module1.shutdown();
+}
+
+#[test]
+fn disconnect_port() {
+    let n = 2;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+
+    let (mut port1_2, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1_2, &mut *port2, n);
+    let (mut port1_3, mut port3) = create_port_pair((&mut module1, "module1"), (&mut module3, "module3"));
+    exchange(&mut *port1_3, &mut *port3, n);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    module3.module.finish_bootstrap().unwrap();
+
+    port1_2.disconnect();
+    port2.disconnect();
+    // Nothing goes through a disconnected port, and it can't be linked again.
+    assert_eq!(port1_2.export_skeleton(&[]), Err(PoolError::Disconnected));
+    assert_eq!(port2.import(&[]), Err(ImportError::Disconnected));
+    let (ipc_arg, _) = Intra::arguments_for_both_ends();
+    assert_eq!(
+        port2.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg, TransportKind::Intra),
+        Err(ModuleError::PortDisconnected {
+            name: "module1".to_owned()
+        })
+    );
+
+    // The link between module1 and module3 keeps working.
+    let called = module3.module.call("call_tokens", &[]).unwrap();
+    assert_eq!(serde_cbor::from_slice::<usize>(&called).unwrap(), n);
+    assert_eq!(module1.report().calls, n);
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn port_initialized() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let mut port1: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let mut port2: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    assert!(!port1.is_initialized());
+    assert!(!port2.is_initialized());
+    assert_eq!(port1.export(&[0]), Err(PoolError::PortNotInitialized));
+    assert_eq!(port2.import(&[]), Err(ImportError::PortNotInitialized));
+
+    let (mut port1, mut port2) = initialize_port_pair(port1, port2);
+    assert!(port1.is_initialized());
+    assert!(port2.is_initialized());
+
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    port1.disconnect();
+    assert!(!port1.is_initialized());
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn import_map() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 3);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let handles = port1.export(&[0, 1, 2]).unwrap();
+    let names = ["pear", "apple", "fig"];
+    let map: HashMap<String, _> = names.iter().map(|name| name.to_string()).zip(handles).collect();
+    let imported = port2.import_map(map).unwrap();
+    // The order of the map doesn't matter.
+    assert_eq!(imported, vec!["apple".to_owned(), "fig".to_owned(), "pear".to_owned()]);
+    assert_eq!(module2.module.imported_names(), imported);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn port_created() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    assert!(module1.report().peers.is_empty());
+
+    let (_port12, _port21) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let (_port13, _port31) = create_port_pair((&mut module1, "module1"), (&mut module3, "module3"));
+    // A port that fails to be created is not told.
+    assert!(module1.module.create_port("module2").is_err());
+    assert_eq!(module1.report().peers, vec!["module2".to_owned(), "module3".to_owned()]);
+    assert_eq!(module2.report().peers, vec!["module1".to_owned()]);
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn import_after_shutdown() {
+    // Serves a module created in this thread, so that its ports outlive the shutdown.
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    // The token is left pending, to be prepared by a module that is gone.
+    let module = fmoudle_rt::create_foundry_module_with_config(
+        module,
+        &[("Token".to_owned(), serde_cbor::to_vec(&0).unwrap())],
+        ModuleConfig {
+            lazy_exports: true,
+            ..Default::default()
+        },
+    );
+    let (ipc_arg1, ipc_arg2) = Intra::arguments_for_both_ends();
+    let join = std::thread::spawn(move || Intra::new(ipc_arg1));
+    let (transport_send2, transport_recv2) = Intra::new(ipc_arg2).split();
+    let (transport_send1, transport_recv1) = join.join().unwrap().split();
+    let _exporter = RtoContext::with_initial_service_export(
+        RtoConfig::default_setup(),
+        transport_send1,
+        transport_recv1,
+        ServiceToExport::new(Box::new(module) as Box<dyn FoundryModule>),
+    );
+    let (importer, module): (_, ServiceToImport<dyn FoundryModule>) =
+        RtoContext::with_initial_service_import(RtoConfig::default_setup(), transport_send2, transport_recv2);
+    let mut module: Box<dyn FoundryModule> = module.into_proxy();
+
+    let mut port: Box<dyn Port> = module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let (ipc_arg, peer_ipc_arg) = Intra::arguments_for_both_ends();
+    let peer = fake_peer(peer_ipc_arg, PORT_PROTOCOL_VERSION);
+    port.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg, TransportKind::Intra).unwrap();
+    let _peer = peer.join().unwrap();
+
+    module.shutdown().unwrap();
+    assert_eq!(port.import(&[]), Err(ImportError::ModuleGone));
+    assert_eq!(port.export_skeleton(&[]), Err(PoolError::ModuleGone));
+    assert_eq!(port.export(&[0]), Err(PoolError::ModuleGone));
+
+    importer.disable_garbage_collection();
+}
+
+#[test]
+fn batched_exchange() {
+    let n = 1000;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let ids: Vec<usize> = (0..n).collect();
+
+    let sent = module1.sent.load(Ordering::SeqCst);
+    let handles = port1.export(&ids).unwrap();
+    assert_eq!(module1.sent.load(Ordering::SeqCst) - sent, 1);
+
+    let slots: Vec<(String, HandleToExchange)> = ids.iter().map(|id| id.to_string()).zip(handles).collect();
+    let sent = module2.sent.load(Ordering::SeqCst);
+    assert_eq!(port2.import(&slots).unwrap().len(), n);
+    assert_eq!(module2.sent.load(Ordering::SeqCst) - sent, 1);
+
+    // Nothing has gone through the link itself.
+    assert_eq!(port1.stats().sent_messages + port1.stats().received_messages, 0);
+
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn finish_bootstrap_with_uninitialized_port() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &[]);
+
+    let _port = module.create_port("peer").unwrap();
+    assert_eq!(
+        module.finish_bootstrap(),
+        Err(ModuleError::PortNotInitialized {
+            name: "peer".to_owned(),
+        })
+    );
+}
+
+#[test]
+fn heartbeat_during_debug() {
+    let module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let foundry_module = &*module.module;
+
+    crossbeam::scope(|scope| {
+        let debug = scope.spawn(|_| foundry_module.debug(b"sleep").unwrap());
+        std::thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        let first = foundry_module.heartbeat();
+        let second = foundry_module.heartbeat();
+        assert!(start.elapsed() < Duration::from_millis(250));
+        assert!(second > first);
+        debug.join().unwrap();
+    })
+    .unwrap();
+
+    module.shutdown();
+}
+
+#[test]
+fn list_ports() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (_port12, _port21) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let port13: Box<dyn Port> = module1.module.create_port("module3").unwrap().unwrap_import().into_proxy();
+    let port_info = |name: &str, initialized| PortInfo {
+        name: name.to_owned(),
+        connected_module: name.to_owned(),
+        initialized,
+        rto_name: if initialized {
+            Some(format!("{}->{}", RtoConfig::default_setup().name, name))
+        } else {
+            None
+        },
+    };
+    assert_eq!(module1.module.list_ports(), vec![port_info("module2", true), port_info("module3", false)]);
+
+    let port31: Box<dyn Port> = module3.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let (_port13, _port31) = initialize_port_pair(port13, port31);
+    assert_eq!(module1.module.list_ports(), vec![port_info("module2", true), port_info("module3", true)]);
+    assert_eq!(module3.module.list_ports(), vec![port_info("module1", true)]);
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn shutdown_drains_calls() {
+    // module1 exports a token that takes a while to answer.
+    let mut module1 = run_module(|args| fmoudle_rt::start::<Intra, Counter>(args));
+    module1
+        .module
+        .initialize(&serde_cbor::to_vec(&false).unwrap(), &[(
+            "LongCallToken".to_owned(),
+            serde_cbor::to_vec(&0).unwrap(),
+        )])
+        .unwrap();
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let foundry_module2 = &*module2.module;
+    crossbeam::scope(|scope| {
+        let call = scope.spawn(|_| foundry_module2.call("call_tokens", &[]).unwrap());
+        std::thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        module1.shutdown();
+        // The shutdown has waited for the call in flight.
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert_eq!(serde_cbor::from_slice::<usize>(&call.join().unwrap()).unwrap(), 1);
+    })
+    .unwrap();
+
+    module2.shutdown();
+}
+
+#[test]
+fn background_job() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    assert!(!module1.report().background_done);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !module1.report().background_done {
+        assert!(Instant::now() < deadline, "The job on the thread pool hasn't run");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn reinitialize() {
+    let exports = vec![("Token".to_owned(), serde_cbor::to_vec(&7).unwrap())];
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &exports);
+    assert_eq!(module.reinitialize(&serde_cbor::to_vec(&false).unwrap(), &exports), Err(ModuleError::NotShutDown));
+    module.finish_bootstrap().unwrap();
+    module.shutdown().unwrap();
+
+    let exports = vec![("Token".to_owned(), serde_cbor::to_vec(&8).unwrap())];
+    module.reinitialize(&serde_cbor::to_vec(&false).unwrap(), &exports).unwrap();
+    let report: Report = serde_cbor::from_slice(&module.debug(&[]).unwrap()).unwrap();
+    assert_eq!(report.prepared, vec![8]);
+    assert_eq!(report.imports_at_bootstrap, None);
+
+    module.finish_bootstrap().unwrap();
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn state() {
+    let mut module = run_module(|args| fmoudle_rt::start::<Intra, Counter>(args));
+    assert_eq!(module.module.state(), ModuleState::Uninitialized);
+    assert_eq!(module.module.create_port("peer").err(), Some(ModuleError::NotInitialized));
+    assert_eq!(module.module.finish_bootstrap(), Err(ModuleError::NotInitialized));
+    module.module.initialize(&serde_cbor::to_vec(&false).unwrap(), &[]).unwrap();
+    assert_eq!(module.module.state(), ModuleState::Initialized);
+    module.module.finish_bootstrap().unwrap();
+    assert_eq!(module.module.state(), ModuleState::Bootstrapped);
+    // The module stops serving once it is shut down, so the rest is checked on one that is not run by `start()`.
+    module.shutdown();
+
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &[]);
+    assert_eq!(module.state(), ModuleState::Initialized);
+    module.finish_bootstrap().unwrap();
+    module.shutdown().unwrap();
+    assert_eq!(module.state(), ModuleState::ShutDown);
+    assert_eq!(module.finish_bootstrap(), Err(ModuleError::ShutDown));
+    module.reinitialize(&serde_cbor::to_vec(&false).unwrap(), &[]).unwrap();
+    assert_eq!(module.state(), ModuleState::Initialized);
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn module_events() {
+    let (events_send, events_recv) = crossbeam::channel::unbounded();
+    let config = ModuleConfig {
+        events: Some(events_send),
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    module1.shutdown();
+    module2.shutdown();
+
+    let events: Vec<ModuleEvent> = events_recv.try_iter().collect();
+    assert_eq!(events, vec![
+        ModuleEvent::Initialized,
+        ModuleEvent::PortCreated {
+            name: "module2".to_owned()
+        },
+        ModuleEvent::BootstrapFinished,
+        ModuleEvent::ShutDown,
+    ]);
+}
+
+/// Removes the id of the module from the name of its worker, like `#3` in `module_worker:counter1#3`.
+fn without_module_id(thread_name: Option<String>) -> Option<String> {
+    thread_name.map(|thread_name| {
+        let start = thread_name.find('#').expect("The worker is not named with the id of its module");
+        let end = thread_name[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(thread_name.len(), |len| start + 1 + len);
+        format!("{}{}", &thread_name[..start], &thread_name[end..])
+    })
+}
+
+#[test]
+fn per_port_threads() {
+    let config = ModuleConfig {
+        per_port_threads: Some(2),
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port12, mut port21) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let (mut port13, mut port31) = create_port_pair((&mut module1, "module1"), (&mut module3, "module3"));
+    exchange(&mut *port12, &mut *port21, 1);
+    exchange(&mut *port13, &mut *port31, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    module3.module.finish_bootstrap().unwrap();
+
+    // The calls from each peer are served by the pool of the port linked with it.
+    let workers = |module: &Module| -> Vec<Option<String>> {
+        let workers: Vec<Option<String>> =
+            serde_cbor::from_slice(&module.module.call("token_workers", &[]).unwrap()).unwrap();
+        workers.into_iter().map(without_module_id).collect()
+    };
+    let port_worker = |port: &str| Some(format!("port_worker:{}({})", std::any::type_name::<Counter>(), port));
+    assert_eq!(workers(&module2), vec![port_worker("module2")]);
+    assert_eq!(workers(&module3), vec![port_worker("module3")]);
+    // The others serve the calls from module1 with their shared pools.
+    assert_eq!(workers(&module1), vec![Some(format!("module_worker:{}", std::any::type_name::<Counter>())); 2]);
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn worker_names() {
+    let config = ModuleConfig {
+        name: Some("counter1".to_owned()),
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let workers = |module: &Module| -> Vec<Option<String>> {
+        let workers: Vec<Option<String>> =
+            serde_cbor::from_slice(&module.module.call("token_workers", &[]).unwrap()).unwrap();
+        workers.into_iter().map(without_module_id).collect()
+    };
+    assert_eq!(workers(&module2), vec![Some("module_worker:counter1".to_owned())]);
+    // A module without a name is named after the type of the user module.
+    assert_eq!(workers(&module1), vec![Some(format!("module_worker:{}", std::any::type_name::<Counter>()))]);
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn compression() {
+    let len = 1 << 20;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let port1: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let port2: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let rto_config = PartialRtoConfig::builder().compression(Some(Compression::Lz4)).build().unwrap();
+    let (mut port1, mut port2) = initialize_port_pair_with_config(port1, port2, rto_config);
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let valid = module2.module.call("check_payloads", &serde_cbor::to_vec(&len).unwrap()).unwrap();
+    assert!(serde_cbor::from_slice::<bool>(&valid).unwrap());
+    // The payload has been compressed on the link.
+    assert!(port1.stats().sent_bytes < len / 10);
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[cfg(unix)]
+#[test]
+fn shared_memory() {
+    let len = 4 << 20;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let mut port1: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let mut port2: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let segment = std::env::temp_dir().join(generate_random_name());
+    let (ipc_arg1, ipc_arg2) = SharedMemoryEnd::arguments_for_both_ends(segment.clone(), 2 * len);
+    let rto_config = PartialRtoConfig::builder().build().unwrap();
+    let rto_config1 = rto_config.clone();
+    let join = std::thread::spawn(move || {
+        port1.initialize(rto_config1, ipc_arg1, TransportKind::SharedMemory).unwrap();
+        port1
+    });
+    port2.initialize(rto_config, ipc_arg2, TransportKind::SharedMemory).unwrap();
+    let mut port1 = join.join().unwrap();
+    // Both ends have mapped the segment, which is unlinked by then.
+    assert!(!segment.exists());
+
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    // The payloads come back through the segment.
+    let valid = module2.module.call("check_payloads", &serde_cbor::to_vec(&len).unwrap()).unwrap();
+    assert!(serde_cbor::from_slice::<bool>(&valid).unwrap());
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn max_message_size() {
+    let max_message_size = 1 << 12;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let port1: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let port2: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let rto_config = PartialRtoConfig::builder()
+        .call_timeout(Some(Duration::from_millis(500)))
+        .max_message_size(Some(max_message_size))
+        .build()
+        .unwrap();
+    let (mut port1, mut port2) = initialize_port_pair_with_config(port1, port2, rto_config);
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let valid = module2.module.call("check_payloads", &serde_cbor::to_vec(&16).unwrap()).unwrap();
+    assert!(serde_cbor::from_slice::<bool>(&valid).unwrap());
+
+    // The answer is too large to be sent, so the caller never gets it.
+    let sent_bytes = port1.stats().sent_bytes;
+    let result = module2.module.call("check_payloads", &serde_cbor::to_vec(&(max_message_size * 2)).unwrap());
+    assert!(matches!(result, Err(ModuleError::Panicked { .. })));
+    assert!(port1.stats().sent_bytes - sent_bytes < max_message_size);
+    // The module that has failed to answer tells why, only once.
+    match module1.module.debug(&[]) {
+        Err(ModuleError::MessageTooLarge {
+            name,
+            size,
+            max,
+        }) => {
+            assert_eq!(name, "module2");
+            assert!(size > max_message_size);
+            assert_eq!(max, max_message_size);
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    module1.module.debug(&[]).unwrap();
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn exported_count() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 3);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    assert_eq!(module1.module.exported_count(), 3);
+    assert_eq!(module2.module.exported_count(), 0);
+
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    assert_eq!(module1.module.exported_count(), 0);
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn duplicate_port() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &[]);
+
+    let _port = module.create_port("peer").unwrap();
+    match module.create_port("peer") {
+        Err(error) => assert_eq!(error, ModuleError::DuplicatePort {
+            name: "peer".to_owned(),
+        }),
+        Ok(_) => panic!("The second port of the same name has been created"),
+    }
+}
+
+#[test]
+fn shutdown_with_uninitialized_port() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &[]);
+
+    let _port = module.create_port("peer").unwrap();
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn finish_bootstrap_twice() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut module = fmoudle_rt::create_foundry_module(module, &[]);
+
+    module.finish_bootstrap().unwrap();
+    assert_eq!(module.finish_bootstrap(), Err(ModuleError::BootstrapAlreadyFinished));
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn check_links() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    assert_eq!(module1.module.check_links().unwrap(), vec![LinkHealth {
+        module: "module2".to_owned(),
+        status: LinkStatus::Alive,
+    }]);
+
+    module2.shutdown();
+    let health = module1.module.check_links().unwrap();
+    assert_eq!(health.len(), 1);
+    assert_eq!(health[0].module, "module2");
+    match &health[0].status {
+        LinkStatus::Dead {
+            ..
+        } => (),
+        status => panic!("The link with the module shut down is {:?}", status),
+    }
+
+    module1.shutdown();
+}
+
+#[test]
+fn metrics() {
+    let n = 3;
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), n);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, n);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let before = module1.module.metrics();
+    assert_eq!(before.exported_count, n);
+    assert_eq!(before.imported_count, n);
+    assert_eq!(before.ports.keys().collect::<Vec<_>>(), vec!["module2"]);
+
+    module2.module.call("call_tokens", &[]).unwrap();
+    let after = module1.module.metrics();
+    assert!(after.uptime > before.uptime);
+    assert_eq!(after.ports["module2"].received_messages - before.ports["module2"].received_messages, n);
+    assert_eq!(after.ports["module2"].sent_messages - before.ports["module2"].sent_messages, n);
+    assert_eq!(after.ports["module2"], port1.stats());
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn import_alias() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let handle = port1.export(&[0]).unwrap()[0];
+    let slots = vec![("a".to_owned(), handle), ("b".to_owned(), handle)];
+    assert_eq!(port2.import(&slots).unwrap(), vec!["a".to_owned(), "b".to_owned()]);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let values: Vec<(String, usize)> =
+        serde_cbor::from_slice(&module2.module.call("named_values", &[]).unwrap()).unwrap();
+    assert_eq!(values, vec![("a".to_owned(), 0), ("b".to_owned(), 0)]);
+    assert_eq!(module1.report().calls, 2);
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn deterministic_link() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let mut port1: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let mut port2: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let link = fmoudle_rt::testing::DeterministicLink::new();
+    let (ipc_arg1, ipc_arg2) = link.arguments_for_both_ends();
+    let rto_config = PartialRtoConfig::builder().build().unwrap();
+    // Neither end waits for the other, so no thread is needed.
+    port1.initialize(rto_config.clone(), ipc_arg1, TransportKind::Deterministic).unwrap();
+    port2.initialize(rto_config, ipc_arg2, TransportKind::Deterministic).unwrap();
+
+    // Exporting and importing don't go through the link.
+    let handles = port1.export(&[0]).unwrap();
+    port2.import(&[("0".to_owned(), handles[0])]).unwrap();
+    assert_eq!(link.pending(), 0);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let caller_module = &*module2.module;
+    crossbeam::scope(|scope| {
+        let caller = scope.spawn(|_| caller_module.call("call_tokens", &[]).unwrap());
+
+        // The call is held until it is delivered to module1.
+        assert!(link.wait_pending(Duration::from_secs(10)));
+        assert_eq!(module1.report().calls, 0);
+        assert_eq!(link.step(), Some(0));
+
+        // So is the reply, which module2 waits for.
+        assert!(link.wait_pending(Duration::from_secs(10)));
+        assert_eq!(module1.report().calls, 1);
+        assert_eq!(link.step(), Some(1));
+        assert_eq!(serde_cbor::from_slice::<usize>(&caller.join().unwrap()).unwrap(), 1);
+    })
+    .unwrap();
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn reject_shutdown() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    module.module.finish_bootstrap().unwrap();
+
+    module.module.call("set_busy", &serde_cbor::to_vec(&true).unwrap()).unwrap();
+    assert_eq!(module.module.shutdown(), Err(ModuleError::ShutdownRejected));
+    assert_eq!(module.module.shutdown_with_timeout(Duration::from_secs(10)), ShutdownOutcome::Rejected);
+    // Nothing has been torn down.
+    assert_eq!(module.report().calls, 0);
+
+    module.module.call("set_busy", &serde_cbor::to_vec(&false).unwrap()).unwrap();
+    module.shutdown();
+}
+
+#[test]
+fn force_shutdown() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    module.module.finish_bootstrap().unwrap();
+
+    module.module.call("set_busy", &serde_cbor::to_vec(&true).unwrap()).unwrap();
+    // The module stops serving once it is shut down, so it can't be asked anymore.
+    module.module.force_shutdown();
+    module.rto_context.disable_garbage_collection();
+}
+
+#[test]
+fn version_mismatch() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let mut port: Box<dyn Port> = module.module.create_port("peer").unwrap().unwrap_import().into_proxy();
+    let (ipc_arg, peer_ipc_arg) = Intra::arguments_for_both_ends();
+    let peer = fake_peer(peer_ipc_arg, PORT_PROTOCOL_VERSION + 1);
+    assert_eq!(
+        port.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg, TransportKind::Intra),
+        Err(ModuleError::VersionMismatch {
+            ours: PORT_PROTOCOL_VERSION,
+            theirs: PORT_PROTOCOL_VERSION + 1,
+        })
+    );
+    let _peer = peer.join().unwrap();
+
+    module.shutdown();
+}
+
+#[test]
+fn malformed_ipc_arg() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let mut port: Box<dyn Port> = module.module.create_port("peer").unwrap().unwrap_import().into_proxy();
+    match port.initialize(PartialRtoConfig::builder().build().unwrap(), b"not an address".to_vec(), TransportKind::Tcp)
+    {
+        Err(ModuleError::TransportFailed {
+            ..
+        }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    module.shutdown();
+}
+
+#[test]
+fn initialize_twice() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (mut port1, port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let (ipc_arg, _) = Intra::arguments_for_both_ends();
+    assert_eq!(
+        port1.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg, TransportKind::Intra),
+        Err(ModuleError::PortAlreadyInitialized {
+            name: "module2".to_owned()
+        })
+    );
+    // The link is left as it was.
+    assert!(port1.is_initialized());
+    assert!(port2.is_initialized());
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn oversized_frame_over_tcp() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (ipc_arg, _) = TcpEnd::arguments_for_both_ends(addr);
+    // Claims a frame of 4 GiB in place of the handshake.
+    let peer = std::thread::spawn(move || loop {
+        if let Ok(mut stream) = std::net::TcpStream::connect(addr) {
+            stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+            break stream
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    });
+    let mut port: Box<dyn Port> = module.module.create_port("peer").unwrap().unwrap_import().into_proxy();
+    match port.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg, TransportKind::Tcp) {
+        Err(ModuleError::TransportFailed {
+            ..
+        }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    let _peer = peer.join().unwrap();
+
+    module.shutdown();
+}
+
+#[test]
+fn init_timeout() {
+    let mut module = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    // Nobody shows up at the other end.
+    let mut port: Box<dyn Port> = module.module.create_port("peer").unwrap().unwrap_import().into_proxy();
+    let (ipc_arg, _peer_ipc_arg) = Intra::arguments_for_both_ends();
+    let config = PartialRtoConfig::builder().init_timeout(Some(Duration::from_millis(100))).build().unwrap();
+    let start = Instant::now();
+    assert_eq!(port.initialize(config, ipc_arg, TransportKind::Intra), Err(ModuleError::PortInitTimeout));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    module.shutdown();
+}
+
+#[test]
+fn runtime_config() {
+    let mut module = run_module(|args| fmoudle_rt::start::<Intra, Counter>(args));
+    let runtime_config = ModuleRuntimeConfig {
+        worker_threads: Some(3),
+        call_timeout: None,
+    };
+    let exports = vec![("0".to_owned(), "Token".to_owned(), serde_cbor::to_vec(&0).unwrap())];
+    let size = module.module.pool_stats().size;
+    // Counter fails to decode the argument, so the runtime is left as it was.
+    match module.module.initialize_with_runtime_config(&[0xff], &exports, runtime_config.clone()) {
+        Err(ModuleError::InitializationFailed {
+            ..
+        }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(module.module.pool_stats().size, size);
+    module
+        .module
+        .initialize_with_runtime_config(&serde_cbor::to_vec(&false).unwrap(), &exports, runtime_config.clone())
+        .unwrap();
+    assert_eq!(module.module.pool_stats().size, 3);
+    assert_eq!(
+        module.module.initialize_with_runtime_config(&serde_cbor::to_vec(&false).unwrap(), &[], runtime_config),
+        Err(ModuleError::AlreadyInitialized)
+    );
+
+    module.module.finish_bootstrap().unwrap();
+    module.shutdown();
+}
+
+#[test]
+fn inflight_calls() {
+    // module1 exports a token that takes a while to answer.
+    let mut module1 = run_module(|args| fmoudle_rt::start::<Intra, Counter>(args));
+    module1
+        .module
+        .initialize(&serde_cbor::to_vec(&false).unwrap(), &[(
+            "LongCallToken".to_owned(),
+            serde_cbor::to_vec(&0).unwrap(),
+        )])
+        .unwrap();
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    let foundry_module2 = &*module2.module;
+    crossbeam::scope(|scope| {
+        let call = scope.spawn(|_| foundry_module2.call("call_tokens", &[]).unwrap());
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(module1.module.inflight_calls(), 1);
+        call.join().unwrap();
+    })
+    .unwrap();
+
+    // The worker may still be finishing the job right after it has replied.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while module1.module.inflight_calls() > 0 {
+        assert!(Instant::now() < deadline, "The call has never finished");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn rto_names() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module3 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+
+    let (_port12, _port21) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    let (_port13, _port31) = create_port_pair((&mut module1, "module1"), (&mut module3, "module3"));
+    let rto_names: Vec<String> = module1.module.list_ports().into_iter().map(|port| port.rto_name.unwrap()).collect();
+    assert_eq!(rto_names.len(), 2);
+    assert_ne!(rto_names[0], rto_names[1]);
+
+    // The name given by the coordinator is kept.
+    let port14: Box<dyn Port> = module1.module.create_port("module4").unwrap().unwrap_import().into_proxy();
+    let port41: Box<dyn Port> = module3.module.create_port("module4").unwrap().unwrap_import().into_proxy();
+    let rto_config = PartialRtoConfig::builder().name("link14").build().unwrap();
+    let (_port14, _port41) = initialize_port_pair_with_config(port14, port41, rto_config);
+    assert_eq!(module1.module.list_ports()[2].rto_name, Some("link14".to_owned()));
+
+    module1.shutdown();
+    module2.shutdown();
+    module3.shutdown();
+}
+
+#[test]
+fn rto_config_hook() {
+    let config = ModuleConfig {
+        rto_config_hook: Some(RtoConfigHook::new(|mut rto_config| {
+            rto_config.name = format!("hooked({})", rto_config.name);
+            rto_config
+        })),
+        ..Default::default()
+    };
+    let mut module1 = create_module_with_config::<Counter>(config, &serde_cbor::to_vec(&false).unwrap(), 1);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 1);
+
+    let port12: Box<dyn Port> = module1.module.create_port("module2").unwrap().unwrap_import().into_proxy();
+    let port21: Box<dyn Port> = module2.module.create_port("module1").unwrap().unwrap_import().into_proxy();
+    let rto_config = PartialRtoConfig::builder().name("link12").build().unwrap();
+    let (mut port12, mut port21) = initialize_port_pair_with_config(port12, port21, rto_config);
+    assert_eq!(module1.module.list_ports()[0].rto_name, Some("hooked(link12)".to_owned()));
+    assert_eq!(module2.module.list_ports()[0].rto_name, Some("link12".to_owned()));
+
+    // The link still works with the rewritten config.
+    exchange(&mut *port12, &mut *port21, 1);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn export_skeleton() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 0);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    // The token is made after the bootstrap, and exported through a port created later.
+    module1.module.call("make_token", &serde_cbor::to_vec(&("dynamic", 7)).unwrap()).unwrap();
+    let port1: Box<dyn Port> = module1.module.create_dynamic_port("module2").unwrap().unwrap_import().into_proxy();
+    let port2: Box<dyn Port> = module2.module.create_dynamic_port("module1").unwrap().unwrap_import().into_proxy();
+    let (mut port1, mut port2) = initialize_port_pair(port1, port2);
+
+    let handles = port1.export_skeleton(&["dynamic".to_owned()]).unwrap();
+    port2.import(&[("dynamic".to_owned(), handles[0])]).unwrap();
+    let values: Vec<(String, usize)> =
+        serde_cbor::from_slice(&module2.module.call("named_values", &[]).unwrap()).unwrap();
+    assert_eq!(values, vec![("dynamic".to_owned(), 7)]);
+    assert_eq!(module1.report().calls, 1);
+
+    // It has been taken by the export.
+    match port1.export_skeleton(&["dynamic".to_owned()]) {
+        Err(error) => assert_eq!(error, PoolError::UnknownDynamicExport {
+            key: "dynamic".to_owned(),
+        }),
+        Ok(_) => panic!("The token has been exported twice"),
+    }
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn mock_port() {
+    let module = Counter::new(&serde_cbor::to_vec(&false).unwrap()).unwrap();
+    let mut port = fmoudle_rt::testing::MockPort::new(module, "exporter");
+
+    let handles = port.export(&[0, 1, 2]).unwrap();
+    let slots: Vec<(String, HandleToExchange)> = (0..3).map(|i| i.to_string()).zip(handles).collect();
+    assert_eq!(port.import(&slots).unwrap().len(), 3);
+
+    assert_eq!(port.slots().len(), 3);
+    assert_eq!(port.stats().imported, 3);
+    assert_eq!(port.module().tokens.len(), 3);
+    assert_eq!(port.module().exporters, vec!["exporter".to_owned(); 3]);
+}
+
+#[test]
+fn imported_count() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 2);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 2);
+    assert_eq!(module1.module.imported_count(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 2);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+
+    for module in &[&module1, &module2] {
+        assert_eq!(module.module.imported_count(), 2);
+        assert_eq!(module.module.imported_names(), vec!["0".to_owned(), "1".to_owned()]);
+    }
+
+    module1.shutdown();
+    module2.shutdown();
+}
+
+#[test]
+fn retained_proxy_count() {
+    let mut module1 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 3);
+    let mut module2 = create_module::<Counter>(&serde_cbor::to_vec(&false).unwrap(), 3);
+    assert_eq!(module1.module.retained_proxy_count(), 0);
+
+    let (mut port1, mut port2) = create_port_pair((&mut module1, "module1"), (&mut module2, "module2"));
+    exchange(&mut *port1, &mut *port2, 3);
+    module1.module.finish_bootstrap().unwrap();
+    module2.module.finish_bootstrap().unwrap();
+    assert_eq!(module1.module.retained_proxy_count(), 3);
+
+    module1.module.call("drop_tokens", &[]).unwrap();
+    assert_eq!(module1.module.retained_proxy_count(), 0);
+    assert_eq!(module2.module.retained_proxy_count(), 3);
+
+    module1.shutdown();
+    module2.shutdown();
+}
@@ -0,0 +1,117 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "prometheus")]
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::coordinator_interface::{FoundryModule, TransportKind};
+use fmoudle_rt::{ImportError, UserModule};
+use parking_lot::RwLock;
+use remote_trait_object::raw_exchange::{HandleToExchange, Skeleton};
+use remote_trait_object::Context as RtoContext;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A module that has nothing to exchange, while its ports are still counted.
+struct Idle;
+
+impl UserModule for Idle {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("Unknown constructor `{}`", ctor_name).into())
+    }
+
+    fn import_service(
+        &mut self,
+        _rto_context: &RtoContext,
+        _exporter_module: &str,
+        name: &str,
+        _handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        Err(ImportError::Rejected {
+            name: name.to_owned(),
+            message: "Nothing to import".to_owned(),
+        })
+    }
+
+    fn debug(&mut self, _arg: &[u8]) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+fn scrape(server: &fmoudle_rt::metrics::MetricsServer) -> String {
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serve_metrics() {
+    let mut module1 = fmoudle_rt::create_foundry_module(Idle, &[]);
+    let mut module2 = fmoudle_rt::create_foundry_module(Idle, &[]);
+    fmoudle_rt::link_two(("module1", &mut module1, &[]), ("module2", &mut module2, &[]), TransportKind::Intra).unwrap();
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    let module1 = Arc::new(RwLock::new(module1));
+    let server =
+        fmoudle_rt::metrics::serve("127.0.0.1:0", Arc::clone(&module1) as Arc<RwLock<dyn FoundryModule>>).unwrap();
+    let response = scrape(&server);
+    assert!(response.starts_with("HTTP/1.0 200 OK"));
+    for name in &[
+        "module_uptime_seconds",
+        "module_inflight_calls",
+        "module_exported_services",
+        "module_imported_services",
+        "module_port_sent_bytes_total{port=\"module2\"}",
+        "module_port_received_bytes_total{port=\"module2\"}",
+    ] {
+        assert!(response.contains(name), "{} is missing in {}", name, response);
+    }
+    // It can be scraped again.
+    assert!(scrape(&server).starts_with("HTTP/1.0 200 OK"));
+    drop(server);
+
+    module1.write().shutdown().unwrap();
+    module2.shutdown().unwrap();
+}
+
+#[test]
+fn drop_with_idle_connection() {
+    let module: Arc<RwLock<dyn FoundryModule>> = Arc::new(RwLock::new(fmoudle_rt::create_foundry_module(Idle, &[])));
+    let server = fmoudle_rt::metrics::serve("127.0.0.1:0", Arc::clone(&module)).unwrap();
+    // Connects without ever sending a request.
+    let _idle = TcpStream::connect(server.local_addr()).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+
+    let start = Instant::now();
+    drop(server);
+    assert!(start.elapsed() < fmoudle_rt::metrics::SCRAPE_TIMEOUT + Duration::from_secs(1));
+
+    module.write().shutdown().unwrap();
+}
@@ -17,16 +17,21 @@
 extern crate foundry_module_rt as fmoudle_rt;
 extern crate foundry_process_sandbox as fproc_sndbx;
 
-use fmoudle_rt::coordinator_interface::{FoundryModule, PartialRtoConfig, Port};
-use fmoudle_rt::UserModule;
+use fmoudle_rt::coordinator_interface::{
+    FoundryModule, ModuleRuntimeConfig, PartialRtoConfig, PoolStats, Port, TransportKind,
+};
+use fmoudle_rt::{ImportError, LinkError, ModuleConfig, ModuleError, PoolError, UserModule};
 use fproc_sndbx::execution::executor::{add_function_pool, execute, Context as ExecutorContext, PlainThread};
 use fproc_sndbx::ipc::{generate_random_name, intra::Intra, Ipc};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use rand::prelude::*;
 use rand::seq::SliceRandom;
 use remote_trait_object::raw_exchange::{import_service_from_handle, HandleToExchange, Skeleton};
 use remote_trait_object::{service, Config as RtoConfig, Context as RtoContext, Service, ServiceRef, ServiceToImport};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
 
 #[service]
 trait Pizza: Service {}
@@ -83,22 +88,33 @@ struct ModuleA {
 }
 
 impl UserModule for ModuleA {
-    fn new(_arg: &[u8]) -> Self {
-        Self {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
             pizza_stores: Default::default(),
             pizza_pool: Default::default(),
             pizza_boxes: Default::default(),
-        }
+        })
     }
 
-    fn prepare_service_to_export(&mut self, _ctor_name: &str, _ctor_arg: &[u8]) -> Skeleton {
-        Skeleton::new(Box::new(SimplePizzaStore {
+    fn prepare_service_to_export(
+        &mut self,
+        _ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Skeleton::new(Box::new(SimplePizzaStore {
             pizza_pool: Arc::clone(&self.pizza_pool),
-        }) as Box<dyn PizzaStore>)
+        }) as Box<dyn PizzaStore>))
     }
 
-    fn import_service(&mut self, rto_context: &RtoContext, _name: &str, handle: HandleToExchange) {
+    fn import_service(
+        &mut self,
+        rto_context: &RtoContext,
+        _exporter_module: &str,
+        _name: &str,
+        handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
         self.pizza_stores.push(import_service_from_handle(rto_context, handle));
+        Ok(())
     }
 
     fn debug(&mut self, _arg: &[u8]) -> Vec<u8> {
@@ -128,6 +144,68 @@ impl UserModule for ModuleA {
     }
 }
 
+#[service]
+trait Tally: Service {
+    fn increment(&self) -> usize;
+}
+
+struct SimpleTally {
+    count: AtomicUsize,
+}
+impl Service for SimpleTally {}
+impl Tally for SimpleTally {
+    fn increment(&self) -> usize {
+        self.count.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// Exports a single tally, and increments the imported ones on `debug`.
+///
+/// It imports only the services named `tally`.
+struct ModuleB {
+    tallies: Vec<Box<dyn Tally>>,
+}
+
+impl UserModule for ModuleB {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
+            tallies: Vec::new(),
+        })
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        _ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Skeleton::new(Box::new(SimpleTally {
+            count: AtomicUsize::new(0),
+        }) as Box<dyn Tally>))
+    }
+
+    fn import_service(
+        &mut self,
+        rto_context: &RtoContext,
+        _exporter_module: &str,
+        name: &str,
+        handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        if name != "tally" {
+            return Err(ImportError::Rejected {
+                name: name.to_owned(),
+                message: "Only a tally can be imported".to_owned(),
+            })
+        }
+        self.tallies.push(import_service_from_handle(rto_context, handle));
+        Ok(())
+    }
+
+    fn debug(&mut self, _arg: &[u8]) -> Vec<u8> {
+        let counts: Vec<usize> = self.tallies.iter().map(|tally| tally.increment()).collect();
+        serde_cbor::to_vec(&counts).unwrap()
+    }
+}
+
 fn execute_module<M: UserModule + 'static>(args: Vec<String>) {
     fmoudle_rt::start::<Intra, M>(args);
 }
@@ -144,7 +222,7 @@ fn create_module(mut exe: ExecutorContext<Intra, PlainThread>, exports: Vec<(Str
         remote_trait_object::Context::with_initial_service_import(config, transport_send, transport_recv);
     let module: Arc<RwLock<dyn FoundryModule>> = module.into_proxy();
 
-    module.write().initialize(&[], &exports);
+    module.write().initialize(&[], &exports).unwrap();
     Module {
         module,
         _exe: exe,
@@ -163,42 +241,69 @@ fn link(modules: &[Module], single_export: bool) {
             let port_name = generate_random_name();
 
             let mut port1: Box<dyn Port> =
-                modules[i].module.write().create_port(&port_name).unwrap_import().into_proxy();
+                modules[i].module.write().create_port(&port_name).unwrap().unwrap_import().into_proxy();
             let mut port2: Box<dyn Port> =
-                modules[j].module.write().create_port(&port_name).unwrap_import().into_proxy();
+                modules[j].module.write().create_port(&port_name).unwrap().unwrap_import().into_proxy();
             let (ipc_arg1, ipc_arg2) = Intra::arguments_for_both_ends();
 
             let join = std::thread::spawn(move || {
-                port1.initialize(PartialRtoConfig::from_rto_config(RtoConfig::default_setup()), ipc_arg1, true);
+                port1.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg1, TransportKind::Intra).unwrap();
                 port1
             });
-            port2.initialize(PartialRtoConfig::from_rto_config(RtoConfig::default_setup()), ipc_arg2, true);
+            port2.initialize(PartialRtoConfig::builder().build().unwrap(), ipc_arg2, TransportKind::Intra).unwrap();
             let mut port1 = join.join().unwrap();
 
-            let handles_1_to_2 = port1.export(&[if single_export {
-                0
-            } else if j > i {
-                // We exported n - 1 services, not n, skipping the index toward itself.
-                j - 1
-            } else {
-                j
-            }]);
-            let handles_2_to_1 = port2.export(&[if single_export {
-                0
-            } else if i > j {
-                // ditto
-                i - 1
-            } else {
-                i
-            }]);
-
-            port1.import(&[("".to_owned(), handles_2_to_1[0])]);
-            port2.import(&[("".to_owned(), handles_1_to_2[0])]);
+            let handles_1_to_2 = port1
+                .export(&[if single_export {
+                    0
+                } else if j > i {
+                    // We exported n - 1 services, not n, skipping the index toward itself.
+                    j - 1
+                } else {
+                    j
+                }])
+                .unwrap();
+            let handles_2_to_1 = port2
+                .export(&[if single_export {
+                    0
+                } else if i > j {
+                    // ditto
+                    i - 1
+                } else {
+                    i
+                }])
+                .unwrap();
+
+            port1.import(&[("".to_owned(), handles_2_to_1[0])]).unwrap();
+            port2.import(&[("".to_owned(), handles_1_to_2[0])]).unwrap();
         }
     }
 
     for module in modules {
-        module.module.write().finish_bootstrap();
+        module.module.write().finish_bootstrap().unwrap();
+    }
+}
+
+/// Does the same as [`link`], but with [`link_all`](fmoudle_rt::link_all) and [`link_mesh`](fmoudle_rt::link_mesh).
+fn link_with_helpers(modules: &[Module], single_export: bool) {
+    let names: Vec<String> = (0..modules.len()).map(|i| i.to_string()).collect();
+    let mut guards: Vec<_> = modules.iter().map(|module| module.module.write()).collect();
+    let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+        .iter()
+        .map(String::as_str)
+        .zip(guards.iter_mut().map(|guard| &mut **guard as &mut dyn FoundryModule))
+        .collect();
+
+    if single_export {
+        fmoudle_rt::link_all(&mut ends, |_, _| vec![(0, "".to_owned())], TransportKind::Intra).unwrap();
+    } else {
+        // We exported n - 1 services, not n, one for each of the others.
+        let topology: Vec<Vec<usize>> = (0..modules.len()).map(|_| (0..modules.len() - 1).collect()).collect();
+        fmoudle_rt::link_mesh(&mut ends, &topology, TransportKind::Intra).unwrap();
+    }
+
+    for guard in guards.iter_mut() {
+        guard.finish_bootstrap().unwrap();
     }
 }
 
@@ -230,7 +335,7 @@ fn multiple() {
     for module in &modules {
         let module = Arc::clone(&module.module);
         joins.push(std::thread::spawn(move || {
-            module.write().debug(&[]);
+            module.write().debug(&[]).unwrap();
         }))
     }
 
@@ -239,7 +344,7 @@ fn multiple() {
     }
 
     for module in modules.into_iter() {
-        module.module.write().shutdown();
+        module.module.write().shutdown().unwrap();
         module.rto_ctx.disable_garbage_collection();
     }
 }
@@ -272,7 +377,7 @@ fn multiple_single_shared_export() {
     for module in &modules {
         let module = Arc::clone(&module.module);
         joins.push(std::thread::spawn(move || {
-            module.write().debug(&[]);
+            module.write().debug(&[]).unwrap();
         }))
     }
 
@@ -281,7 +386,500 @@ fn multiple_single_shared_export() {
     }
 
     for module in modules.into_iter() {
-        module.module.write().shutdown();
+        module.module.write().shutdown().unwrap();
         module.rto_ctx.disable_garbage_collection();
     }
 }
+
+/// Creates `n` modules running `ModuleA`, each exporting a service for every other module or a single shared one.
+fn create_modules_a(n: usize, single_export: bool) -> Vec<Module> {
+    (0..n)
+        .map(|_| {
+            let name = generate_random_name();
+            add_function_pool(name.clone(), Arc::new(execute_module::<ModuleA>));
+            let executor = execute::<Intra, PlainThread>(&name).unwrap();
+            let exports: Vec<(String, Vec<u8>)> = (0..if single_export {
+                1
+            } else {
+                n - 1
+            })
+                .map(|_| ("".to_owned(), vec![]))
+                .collect();
+            create_module(executor, exports)
+        })
+        .collect()
+}
+
+fn debug_and_shutdown(modules: Vec<Module>) {
+    let joins: Vec<_> = modules
+        .iter()
+        .map(|module| {
+            let module = Arc::clone(&module.module);
+            std::thread::spawn(move || {
+                module.write().debug(&[]).unwrap();
+            })
+        })
+        .collect();
+    for join in joins.into_iter() {
+        join.join().unwrap();
+    }
+
+    for module in modules.into_iter() {
+        module.module.write().shutdown().unwrap();
+        module.rto_ctx.disable_garbage_collection();
+    }
+}
+
+#[test]
+fn multiple_link_mesh() {
+    let modules = create_modules_a(10, false);
+    link_with_helpers(&modules, false);
+    debug_and_shutdown(modules);
+}
+
+#[test]
+fn multiple_link_all_single_shared_export() {
+    let modules = create_modules_a(10, true);
+    link_with_helpers(&modules, true);
+    debug_and_shutdown(modules);
+}
+
+#[test]
+fn export_same_index_to_multiple_ports() {
+    let n = 4;
+    let mut modules = Vec::new();
+    for i in 0..n {
+        let name = generate_random_name();
+        add_function_pool(name.clone(), Arc::new(execute_module::<ModuleB>));
+        let executor = execute::<Intra, PlainThread>(&name).unwrap();
+        // Only the first module exports, and the rest import it.
+        let exports = if i == 0 {
+            vec![("".to_owned(), vec![])]
+        } else {
+            vec![]
+        };
+        modules.push(create_module(executor, exports));
+    }
+
+    let names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    let mut guards: Vec<_> = modules.iter().map(|module| module.module.write()).collect();
+    let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+        .iter()
+        .map(String::as_str)
+        .zip(guards.iter_mut().map(|guard| &mut **guard as &mut dyn FoundryModule))
+        .collect();
+    fmoudle_rt::link_all(
+        &mut ends,
+        |i, _| {
+            if i == 0 {
+                vec![(0, "tally".to_owned())]
+            } else {
+                vec![]
+            }
+        },
+        TransportKind::Intra,
+    )
+    .unwrap();
+    for guard in guards.iter_mut() {
+        guard.finish_bootstrap().unwrap();
+    }
+    drop(guards);
+
+    // Every proxy reaches the same tally, which counts the calls from all of them.
+    let mut counts = Vec::new();
+    for module in &modules[1..] {
+        let result: Vec<usize> = serde_cbor::from_slice(&module.module.write().debug(&[]).unwrap()).unwrap();
+        counts.extend(result);
+    }
+    assert_eq!(counts, vec![1, 2, 3]);
+
+    for module in modules.into_iter() {
+        module.module.write().shutdown().unwrap();
+        module.rto_ctx.disable_garbage_collection();
+    }
+}
+
+#[test]
+fn inconsistent_topology() {
+    let n = 3;
+    let mut modules: Vec<_> = (0..n)
+        .map(|_| {
+            let exports: Vec<(String, Vec<u8>)> = (0..n - 1).map(|_| ("".to_owned(), vec![])).collect();
+            fmoudle_rt::create_foundry_module(ModuleA::new(&[]).unwrap(), &exports)
+        })
+        .collect();
+    let names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+        .iter()
+        .map(String::as_str)
+        .zip(modules.iter_mut().map(|module| module as &mut dyn FoundryModule))
+        .collect();
+
+    // The second module exports its first service twice, leaving the other one behind.
+    let topology = vec![vec![0, 1], vec![0, 0], vec![0, 1]];
+    match fmoudle_rt::link_mesh(&mut ends, &topology, TransportKind::Intra) {
+        Err(LinkError::Topology {
+            module,
+            ..
+        }) => assert_eq!(module, "1"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Nothing has been linked.
+    for module in modules.iter_mut() {
+        assert!(module.list_ports().is_empty());
+    }
+}
+
+#[test]
+fn dry_link() {
+    let n = 3;
+    let modules: Vec<_> = (0..n)
+        .map(|_| {
+            let exports: Vec<(String, Vec<u8>)> = (0..n - 1).map(|_| ("".to_owned(), vec![])).collect();
+            fmoudle_rt::create_foundry_module(ModuleA::new(&[]).unwrap(), &exports)
+        })
+        .collect();
+    let names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    let ends: Vec<(&str, &dyn FoundryModule)> =
+        names.iter().map(String::as_str).zip(modules.iter().map(|module| module as &dyn FoundryModule)).collect();
+
+    let exports = |i: usize, j: usize| {
+        let peer = if j > i {
+            j - 1
+        } else {
+            j
+        };
+        vec![(peer, i.to_string())]
+    };
+    fmoudle_rt::dry_link(&ends, exports).unwrap();
+    // The second module exports a service that it doesn't have.
+    assert_eq!(
+        fmoudle_rt::dry_link(&ends, |i, j| if i == 1 {
+            vec![(5, i.to_string())]
+        } else {
+            exports(i, j)
+        }),
+        Err(LinkError::Export {
+            module: "1".to_owned(),
+            error: PoolError::IndexOutOfRange {
+                index: 5,
+                len: 2
+            },
+        })
+    );
+    drop(ends);
+
+    // Nothing has been linked.
+    for module in modules.iter() {
+        assert!(module.list_ports().is_empty());
+    }
+}
+
+#[test]
+fn shared_thread_pool() {
+    let n = 3;
+    let thread_pool = Arc::new(Mutex::new(ThreadPool::new(4)));
+    let mut modules: Vec<_> = (0..n)
+        .map(|_| {
+            fmoudle_rt::create_foundry_module_with_pool(
+                ModuleB::new(&[]).unwrap(),
+                &[("Tally".to_owned(), vec![])],
+                Arc::clone(&thread_pool),
+            )
+        })
+        .collect();
+    let names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+        .iter()
+        .map(String::as_str)
+        .zip(modules.iter_mut().map(|module| module as &mut dyn FoundryModule))
+        .collect();
+    fmoudle_rt::link_all(&mut ends, |_, _| vec![(0, "tally".to_owned())], TransportKind::Intra).unwrap();
+    drop(ends);
+    for module in modules.iter_mut() {
+        module.finish_bootstrap().unwrap();
+    }
+
+    // Each tally is incremented once by each of the other modules.
+    let mut counts: Vec<usize> = Vec::new();
+    for module in modules.iter() {
+        let module_counts: Vec<usize> = serde_cbor::from_slice(&module.debug(&[]).unwrap()).unwrap();
+        assert_eq!(module_counts.len(), n - 1);
+        counts.extend(module_counts);
+    }
+    counts.sort_unstable();
+    assert_eq!(counts, vec![1, 1, 1, 2, 2, 2]);
+
+    for module in modules.iter_mut() {
+        module.shutdown().unwrap();
+    }
+}
+
+#[test]
+fn reject_import() {
+    let mut module1 = fmoudle_rt::create_foundry_module(ModuleB::new(&[]).unwrap(), &[("Tally".to_owned(), vec![])]);
+    let mut module2 = fmoudle_rt::create_foundry_module(ModuleB::new(&[]).unwrap(), &[]);
+
+    // The link-desc names the tally wrongly.
+    let exports = vec![(0, "counter".to_owned())];
+    match fmoudle_rt::link_two(("1", &mut module1, &exports), ("2", &mut module2, &[]), TransportKind::Intra) {
+        Err(LinkError::Import {
+            module,
+            error: ImportError::Failed {
+                errors,
+            },
+        }) => {
+            assert_eq!(module, "2");
+            assert!(matches!(errors.as_slice(), [ImportError::Rejected { name, .. }] if name == "counter"));
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+}
+
+#[test]
+fn pool_stats() {
+    let thread_pool = Arc::new(Mutex::new(ThreadPool::new(2)));
+    let mut module =
+        fmoudle_rt::create_foundry_module_with_pool(ModuleB::new(&[]).unwrap(), &[], Arc::clone(&thread_pool));
+    module.finish_bootstrap().unwrap();
+    assert_eq!(module.pool_stats(), PoolStats {
+        size: 2,
+        active: 0,
+        queued: 0,
+    });
+
+    // Three jobs wait to be released, one more than the workers.
+    let (release_send, release_recv) = crossbeam::channel::unbounded::<()>();
+    for _ in 0..3 {
+        let release_recv = release_recv.clone();
+        thread_pool.lock().execute(move || {
+            let _ = release_recv.recv();
+        });
+    }
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while module.pool_stats().active < 2 {
+        assert!(Instant::now() < deadline, "The workers have never picked up the jobs");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(module.pool_stats(), PoolStats {
+        size: 2,
+        active: 2,
+        queued: 1,
+    });
+
+    drop(release_send);
+    thread_pool.lock().join();
+    assert_eq!(module.pool_stats(), PoolStats {
+        size: 2,
+        active: 0,
+        queued: 0,
+    });
+
+    module.shutdown().unwrap();
+    // The pool is not the module's own to resize.
+    let runtime_config = ModuleRuntimeConfig {
+        worker_threads: Some(3),
+        call_timeout: None,
+    };
+    match module.initialize_with_runtime_config(&[], &[], runtime_config) {
+        Err(ModuleError::InitializationFailed {
+            ..
+        }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(thread_pool.lock().max_count(), 2);
+}
+
+#[test]
+fn create_ports() {
+    let n = 5;
+    let mut hub = fmoudle_rt::create_foundry_module(ModuleB::new(&[]).unwrap(), &[("Tally".to_owned(), vec![])]);
+    let mut peers: Vec<_> =
+        (0..n).map(|_| fmoudle_rt::create_foundry_module(ModuleB::new(&[]).unwrap(), &[])).collect();
+
+    // The duplicate is rejected on its own, leaving the others created.
+    let mut names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    names.push("0".to_owned());
+    let mut ports = hub.create_ports(&names);
+    assert_eq!(ports.len(), n + 1);
+    match ports.pop().unwrap() {
+        Err(error) => assert_eq!(error, ModuleError::DuplicatePort {
+            name: "0".to_owned()
+        }),
+        Ok(_) => panic!("Duplicate port has been created"),
+    }
+
+    for (hub_port, peer) in ports.into_iter().zip(peers.iter_mut()) {
+        let mut hub_port: Box<dyn Port> = hub_port.unwrap().unwrap_import().into_proxy();
+        let mut peer_port: Box<dyn Port> = peer.create_port("hub").unwrap().unwrap_import().into_proxy();
+        let (hub_ipc_arg, peer_ipc_arg) = Intra::arguments_for_both_ends();
+        let join = std::thread::spawn(move || {
+            hub_port
+                .initialize(PartialRtoConfig::builder().build().unwrap(), hub_ipc_arg, TransportKind::Intra)
+                .unwrap();
+            hub_port
+        });
+        peer_port.initialize(PartialRtoConfig::builder().build().unwrap(), peer_ipc_arg, TransportKind::Intra).unwrap();
+        let mut hub_port = join.join().unwrap();
+        let handles = hub_port.export(&[0]).unwrap();
+        peer_port.import(&[("tally".to_owned(), handles[0])]).unwrap();
+    }
+    hub.finish_bootstrap().unwrap();
+    for peer in peers.iter_mut() {
+        peer.finish_bootstrap().unwrap();
+    }
+
+    // Every peer reaches the tally of the hub through its own port.
+    let mut counts = Vec::new();
+    for peer in peers.iter() {
+        let result: Vec<usize> = serde_cbor::from_slice(&peer.debug(&[]).unwrap()).unwrap();
+        counts.extend(result);
+    }
+    assert_eq!(counts, vec![1, 2, 3, 4, 5]);
+
+    hub.shutdown().unwrap();
+    for peer in peers.iter_mut() {
+        peer.shutdown().unwrap();
+    }
+}
+
+#[test]
+fn export_concurrently() {
+    let n = 8;
+    let config = ModuleConfig {
+        lazy_exports: true,
+        ..Default::default()
+    };
+    let mut hub = fmoudle_rt::create_foundry_module_with_config(
+        ModuleB::new(&[]).unwrap(),
+        &[("Tally".to_owned(), vec![])],
+        config,
+    );
+    let mut peers: Vec<_> =
+        (0..n).map(|_| fmoudle_rt::create_foundry_module(ModuleB::new(&[]).unwrap(), &[])).collect();
+
+    let names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    let hub_ports = hub.create_ports(&names);
+    let peer_ports: Vec<_> = peers.iter_mut().map(|peer| peer.create_port("hub").unwrap()).collect();
+
+    // Every link is made at the same time, all exporting the one tally that is prepared on the first export.
+    let joins: Vec<_> = hub_ports
+        .into_iter()
+        .zip(peer_ports)
+        .map(|(hub_port, peer_port)| {
+            let mut hub_port: Box<dyn Port> = hub_port.unwrap().unwrap_import().into_proxy();
+            let mut peer_port: Box<dyn Port> = peer_port.unwrap_import().into_proxy();
+            std::thread::spawn(move || {
+                let (hub_ipc_arg, peer_ipc_arg) = Intra::arguments_for_both_ends();
+                let join = std::thread::spawn(move || {
+                    hub_port
+                        .initialize(PartialRtoConfig::builder().build().unwrap(), hub_ipc_arg, TransportKind::Intra)
+                        .unwrap();
+                    hub_port
+                });
+                peer_port
+                    .initialize(PartialRtoConfig::builder().build().unwrap(), peer_ipc_arg, TransportKind::Intra)
+                    .unwrap();
+                let mut hub_port = join.join().unwrap();
+                let handles = hub_port.export(&[0]).unwrap();
+                peer_port.import(&[("tally".to_owned(), handles[0])]).unwrap();
+            })
+        })
+        .collect();
+    for join in joins {
+        join.join().unwrap();
+    }
+    hub.finish_bootstrap().unwrap();
+    for peer in peers.iter_mut() {
+        peer.finish_bootstrap().unwrap();
+    }
+
+    // They have all reached the same tally.
+    let mut counts = Vec::new();
+    for peer in peers.iter() {
+        let result: Vec<usize> = serde_cbor::from_slice(&peer.debug(&[]).unwrap()).unwrap();
+        counts.extend(result);
+    }
+    assert_eq!(counts, (1..=n).collect::<Vec<_>>());
+
+    hub.shutdown().unwrap();
+    for peer in peers.iter_mut() {
+        peer.shutdown().unwrap();
+    }
+}
+
+#[test]
+fn drop_without_shutdown() {
+    let n = 3;
+    let mut modules: Vec<_> = (0..n)
+        .map(|_| fmoudle_rt::create_foundry_module(ModuleB::new(&[]).unwrap(), &[("Tally".to_owned(), vec![])]))
+        .collect();
+    let names: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    let mut ends: Vec<(&str, &mut dyn FoundryModule)> = names
+        .iter()
+        .map(String::as_str)
+        .zip(modules.iter_mut().map(|module| module as &mut dyn FoundryModule))
+        .collect();
+    fmoudle_rt::link_all(&mut ends, |_, _| vec![(0, "tally".to_owned())], TransportKind::Intra).unwrap();
+    drop(ends);
+    for module in modules.iter_mut() {
+        module.finish_bootstrap().unwrap();
+    }
+    for module in modules.iter() {
+        module.debug(&[]).unwrap();
+    }
+
+    // The modules tear themselves down when they are dropped, without hanging.
+    let (dropped_send, dropped_recv) = crossbeam::channel::bounded(1);
+    std::thread::spawn(move || {
+        drop(modules);
+        dropped_send.send(()).unwrap();
+    });
+    dropped_recv.recv_timeout(Duration::from_secs(10)).unwrap();
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn multiple_with_harness() {
+    let n = 10;
+    let mut harness = fmoudle_rt::testing::TestHarness::new();
+    for _ in 0..n {
+        // we use n-1 since we don't prepare a service for its own.
+        let exports: Vec<(String, Vec<u8>)> = (0..n - 1).map(|_| ("".to_owned(), vec![])).collect();
+        harness = harness.add_module::<ModuleA>(&[], &exports);
+    }
+    let modules = harness
+        .link_exports(|i, j| {
+            vec![(
+                if j > i {
+                    j - 1
+                } else {
+                    j
+                },
+                "".to_owned(),
+            )]
+        })
+        .build();
+
+    let mut joins = Vec::new();
+    for module in &modules {
+        let module = Arc::clone(&module.module);
+        joins.push(std::thread::spawn(move || {
+            module.write().debug(&[]).unwrap();
+        }))
+    }
+
+    for join in joins.into_iter() {
+        join.join().unwrap();
+    }
+
+    for module in modules.into_iter() {
+        module.shutdown();
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::coordinator_interface::FoundryModule;
+use fmoudle_rt::{ImportError, ModuleConfig, PanicHook, ThreadPoolHandle, UserModule};
+use parking_lot::Mutex;
+use remote_trait_object::raw_exchange::{HandleToExchange, Skeleton};
+use remote_trait_object::Context as RtoContext;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Panics with the argument on `debug`, in a worker of the module.
+#[derive(Default)]
+struct Panicker {
+    thread_pool: Option<ThreadPoolHandle>,
+}
+
+impl UserModule for Panicker {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self::default())
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!("Unknown constructor `{}`", ctor_name).into())
+    }
+
+    fn import_service(
+        &mut self,
+        _rto_context: &RtoContext,
+        _exporter_module: &str,
+        name: &str,
+        _handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        Err(ImportError::Rejected {
+            name: name.to_owned(),
+            message: "Nothing to import".to_owned(),
+        })
+    }
+
+    fn bootstrap_finished(&mut self, thread_pool: ThreadPoolHandle) {
+        self.thread_pool = Some(thread_pool);
+    }
+
+    fn debug(&mut self, arg: &[u8]) -> Vec<u8> {
+        let message = String::from_utf8_lossy(arg).into_owned();
+        self.thread_pool.as_ref().unwrap().execute(move || panic!("{}", message));
+        Vec::new()
+    }
+}
+
+fn wait_for_panics(panics: &Mutex<Vec<(String, String)>>, len: usize) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while panics.lock().len() < len {
+        assert!(Instant::now() < deadline, "The panic has never been told");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn panic_hook() {
+    let panics = Arc::new(Mutex::new(Vec::new()));
+    let panics_ = Arc::clone(&panics);
+    let config = ModuleConfig {
+        panic_hook: Some(PanicHook::new(move |info, module_name| {
+            let message = info.payload().downcast_ref::<String>().cloned().unwrap_or_default();
+            panics_.lock().push((module_name.to_owned(), message));
+        })),
+        ..ModuleConfig::default()
+    };
+    let mut module = fmoudle_rt::create_foundry_module_with_config(Panicker::default(), &[], config);
+    module.finish_bootstrap().unwrap();
+
+    module.debug(b"oops").unwrap();
+    wait_for_panics(&panics, 1);
+    assert_eq!(*panics.lock(), vec![(std::any::type_name::<Panicker>().to_owned(), "oops".to_owned())]);
+    // A panic in a thread that is not a worker of the module goes on to the hook of the process.
+    assert!(std::panic::catch_unwind(|| panic!("elsewhere")).is_err());
+    assert_eq!(panics.lock().len(), 1);
+
+    // It is registered again along with the module.
+    module.shutdown().unwrap();
+    module.reinitialize(&[], &[]).unwrap();
+    module.finish_bootstrap().unwrap();
+    module.debug(b"again").unwrap();
+    wait_for_panics(&panics, 2);
+    assert_eq!(panics.lock()[1].1, "again");
+
+    module.shutdown().unwrap();
+}
+
+#[test]
+fn panic_hooks_of_same_name() {
+    // Both modules are named after the type of the user module.
+    let hooked = |panics: &Arc<Mutex<Vec<(String, String)>>>| {
+        let panics = Arc::clone(panics);
+        let config = ModuleConfig {
+            panic_hook: Some(PanicHook::new(move |info, module_name| {
+                let message = info.payload().downcast_ref::<String>().cloned().unwrap_or_default();
+                panics.lock().push((module_name.to_owned(), message));
+            })),
+            ..ModuleConfig::default()
+        };
+        let mut module = fmoudle_rt::create_foundry_module_with_config(Panicker::default(), &[], config);
+        module.finish_bootstrap().unwrap();
+        module
+    };
+    let panics1 = Arc::new(Mutex::new(Vec::new()));
+    let panics2 = Arc::new(Mutex::new(Vec::new()));
+    let mut module1 = hooked(&panics1);
+    let mut module2 = hooked(&panics2);
+
+    module2.debug(b"second").unwrap();
+    wait_for_panics(&panics2, 1);
+    module1.debug(b"first").unwrap();
+    wait_for_panics(&panics1, 1);
+    assert_eq!(panics1.lock()[0].1, "first");
+    assert_eq!(panics2.lock()[0].1, "second");
+    assert_eq!(panics1.lock().len(), 1);
+    assert_eq!(panics2.lock().len(), 1);
+
+    module1.shutdown().unwrap();
+    module2.shutdown().unwrap();
+}
@@ -0,0 +1,113 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::coordinator_interface::{FoundryModule, TransportKind};
+use fmoudle_rt::{ImportError, ModuleError, UserModule};
+use parking_lot::RwLock;
+use remote_trait_object::raw_exchange::{import_service_from_handle, HandleToExchange, Skeleton};
+use remote_trait_object::{service, Context as RtoContext, Service};
+use std::sync::Arc;
+
+#[service]
+trait Board: Service {
+    fn read(&self) -> String;
+}
+
+struct Notice {
+    message: String,
+}
+impl Service for Notice {}
+impl Board for Notice {
+    fn read(&self) -> String {
+        self.message.clone()
+    }
+}
+
+/// Exports its notice, which it keeps posting on by `call`, and reads the imported one on `debug`.
+struct Noticeboard {
+    notice: Arc<RwLock<Notice>>,
+    imported: Option<Box<dyn Board>>,
+}
+
+impl UserModule for Noticeboard {
+    fn new(_arg: &[u8]) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self {
+            notice: Arc::new(RwLock::new(Notice {
+                message: String::new(),
+            })),
+            imported: None,
+        })
+    }
+
+    fn prepare_service_to_export(
+        &mut self,
+        _ctor_name: &str,
+        _ctor_arg: &[u8],
+    ) -> Result<Skeleton, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Skeleton::new(Arc::clone(&self.notice) as Arc<RwLock<dyn Board>>))
+    }
+
+    fn import_service(
+        &mut self,
+        rto_context: &RtoContext,
+        _exporter_module: &str,
+        _name: &str,
+        handle: HandleToExchange,
+    ) -> Result<(), ImportError> {
+        self.imported = Some(import_service_from_handle(rto_context, handle));
+        Ok(())
+    }
+
+    fn call(&mut self, method: &str, arg: &[u8]) -> Result<Vec<u8>, ModuleError> {
+        match method {
+            "post" => {
+                self.notice.write().message = serde_cbor::from_slice(arg).unwrap();
+                Ok(Vec::new())
+            }
+            _ => Err(ModuleError::UnknownMethod {
+                method: method.to_owned(),
+            }),
+        }
+    }
+
+    fn debug(&mut self, _arg: &[u8]) -> Vec<u8> {
+        serde_cbor::to_vec(&self.imported.as_ref().unwrap().read()).unwrap()
+    }
+}
+
+#[test]
+fn shared_service_state() {
+    let mut module1 =
+        fmoudle_rt::create_foundry_module(Noticeboard::new(&[]).unwrap(), &[("Notice".to_owned(), vec![])]);
+    let mut module2 = fmoudle_rt::create_foundry_module(Noticeboard::new(&[]).unwrap(), &[]);
+    let exports = vec![(0, "notice".to_owned())];
+    fmoudle_rt::link_two(("module1", &mut module1, &exports), ("module2", &mut module2, &[]), TransportKind::Intra)
+        .unwrap();
+    module1.finish_bootstrap().unwrap();
+    module2.finish_bootstrap().unwrap();
+
+    // module2 sees what module1 posts on the service after it has been exported.
+    for message in &["hello", "bye"] {
+        module1.call("post", &serde_cbor::to_vec(message).unwrap()).unwrap();
+        let read: String = serde_cbor::from_slice(&module2.debug(&[]).unwrap()).unwrap();
+        assert_eq!(read, *message);
+    }
+
+    module2.shutdown().unwrap();
+    module1.shutdown().unwrap();
+}
@@ -0,0 +1,99 @@
+// Copyright 2020 Kodebox, Inc.
+// This file is part of CodeChain.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate foundry_module_rt as fmoudle_rt;
+
+use fmoudle_rt::CallTimeoutError;
+use fproc_sndbx::ipc::{intra::Intra, Ipc};
+use remote_trait_object::{
+    service, Config as RtoConfig, Context as RtoContext, Service, ServiceToExport, ServiceToImport,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[service]
+trait Sleeper: Service {
+    fn sleep(&self, millis: u64) -> u64;
+}
+
+struct SimpleSleeper;
+
+impl Service for SimpleSleeper {}
+
+impl Sleeper for SimpleSleeper {
+    fn sleep(&self, millis: u64) -> u64 {
+        std::thread::sleep(Duration::from_millis(millis));
+        millis
+    }
+}
+
+#[test]
+fn call_with_timeout() {
+    let (ipc_arg1, ipc_arg2) = Intra::arguments_for_both_ends();
+    let join = std::thread::spawn(move || Intra::new(ipc_arg1));
+    let (transport_send2, transport_recv2) = Intra::new(ipc_arg2).split();
+    let (transport_send1, transport_recv1) = join.join().unwrap().split();
+    let exporter = RtoContext::with_initial_service_export(
+        RtoConfig::default_setup(),
+        transport_send1,
+        transport_recv1,
+        ServiceToExport::new(Box::new(SimpleSleeper) as Box<dyn Sleeper>),
+    );
+    let (importer, sleeper): (_, ServiceToImport<dyn Sleeper>) =
+        RtoContext::with_initial_service_import(RtoConfig::default_setup(), transport_send2, transport_recv2);
+    let sleeper: Arc<dyn Sleeper> = sleeper.into_proxy();
+
+    let sleep = |millis: u64, timeout: Duration| {
+        let sleeper = Arc::clone(&sleeper);
+        fmoudle_rt::call_with_timeout(move || sleeper.sleep(millis), timeout)
+    };
+    assert_eq!(sleep(500, Duration::from_millis(50)), Err(CallTimeoutError::TimedOut));
+    assert_eq!(sleep(100, Duration::from_secs(5)), Ok(100));
+
+    // Waits for the abandoned call to be answered.
+    std::thread::sleep(Duration::from_millis(500));
+
+    // The abandoned calls keep their workers, so no more call is made until they are answered.
+    for _ in 0..fmoudle_rt::MAX_OUTSTANDING_CALLS {
+        assert_eq!(sleep(200, Duration::from_millis(1)), Err(CallTimeoutError::TimedOut));
+    }
+    assert_eq!(
+        sleep(0, Duration::from_secs(5)),
+        Err(CallTimeoutError::TooManyOutstanding {
+            limit: fmoudle_rt::MAX_OUTSTANDING_CALLS,
+        })
+    );
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match sleep(0, Duration::from_secs(5)) {
+            Err(CallTimeoutError::TooManyOutstanding {
+                ..
+            }) => {
+                assert!(Instant::now() < deadline, "The abandoned calls have never been answered");
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            result => {
+                assert_eq!(result, Ok(0));
+                break
+            }
+        }
+    }
+
+    drop(sleep);
+    drop(sleeper);
+    exporter.disable_garbage_collection();
+    importer.disable_garbage_collection();
+}